@@ -0,0 +1,74 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::BasePath;
+use super::BasePathBuf;
+
+/// The capabilities used to create and modify base paths.
+///
+/// Methods of [`BasePath`] and [`BasePathBuf`] that create or modify paths
+/// accept an implementation of this trait, instead of one closure for each
+/// capability. The provided methods behave like the standard library, so
+/// implementations only need to override the capabilities they restrict or
+/// observe. [`DefaultAuthority`] overrides none of them.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::path::PathBuf;
+///
+/// use normpath::Authority;
+/// use normpath::BasePathBuf;
+///
+/// // Refuses to remove the root directory.
+/// struct KeepRoot;
+///
+/// impl Authority for KeepRoot {
+///     fn pop(&self, base: &mut PathBuf) -> bool {
+///         base.parent().is_some_and(|x| x.parent().is_some()) && base.pop()
+///     }
+/// }
+///
+/// if !cfg!(windows) {
+///     let mut path = BasePathBuf::try_new("/foo").unwrap();
+///     assert!(!path.pop_unchecked(&KeepRoot));
+///     assert_eq!(Path::new("/foo"), path);
+/// }
+/// ```
+pub trait Authority {
+    /// Creates an owned copy of `base`, which will then be modified.
+    ///
+    /// This method is called by [`BasePath::join`].
+    #[inline]
+    fn generate(&self, base: &BasePath) -> BasePathBuf {
+        base.to_owned()
+    }
+
+    /// Appends `path` to `base`, like [`PathBuf::push`].
+    ///
+    /// This method is called by [`BasePathBuf::push`] for non-empty paths.
+    /// On Windows, joining must account for prefixes, so paths are joined by
+    /// this crate instead and only [`pop`] is called.
+    ///
+    /// [`pop`]: Self::pop
+    #[inline]
+    fn push(&self, base: &mut PathBuf, path: &Path) {
+        base.push(path);
+    }
+
+    /// Removes the last component of `base`, like [`PathBuf::pop`].
+    ///
+    /// This method is called by [`BasePathBuf::pop`] and
+    /// [`BasePathBuf::pop_unchecked`].
+    #[inline]
+    fn pop(&self, base: &mut PathBuf) -> bool {
+        base.pop()
+    }
+}
+
+/// An [`Authority`] with the same capabilities as the standard library.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DefaultAuthority;
+
+impl Authority for DefaultAuthority {}
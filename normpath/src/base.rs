@@ -15,10 +15,15 @@ use std::path::Components;
 use std::path::Path;
 use std::path::PathBuf;
 
+use super::beneath;
+use super::error::EscapeError;
 use super::error::MissingPrefixBufError;
 use super::error::MissingPrefixError;
 use super::error::ParentError;
+use super::error::ResolveError;
 use super::imp;
+use super::lexical;
+use super::Authority;
 use super::PathExt;
 
 fn cow_path_into_base_path(path: Cow<'_, Path>) -> Cow<'_, BasePath> {
@@ -146,8 +151,7 @@ impl BasePath {
     #[inline]
     #[must_use]
     pub fn as_path(&self) -> &Path {
-        // NOTE: This is a loophole and should be revisited later
-        unsafe { std::mem::transmute(&self.0) }
+        Path::new(&self.0)
     }
 
     /// Equivalent to [`Path::canonicalize`].
@@ -266,13 +270,14 @@ impl BasePath {
     /// use std::path::Path;
     ///
     /// use normpath::BasePath;
+    /// use normpath::DefaultAuthority;
     ///
     /// if cfg!(windows) {
     ///     assert_eq!(
     ///         Path::new(r"\\?\foo\baz\test.rs"),
     ///         BasePath::try_new(r"\\?\foo\bar")
     ///             .unwrap()
-    ///             .join("../baz/test.rs"),
+    ///             .join("../baz/test.rs", &DefaultAuthority),
     ///     );
     /// }
     /// ```
@@ -281,15 +286,136 @@ impl BasePath {
     /// [`parent`]: Self::parent
     /// [verbatim]: ::std::path::Prefix::is_verbatim
     #[inline]
-    pub fn join<P, F: Fn(&mut PathBuf, &Path)>(&self, path: P, push: F) -> BasePathBuf
+    pub fn join<P, A>(&self, path: P, authority: &A) -> BasePathBuf
     where
         P: AsRef<Path>,
+        A: Authority + ?Sized,
     {
-        let mut base = self.to_owned();
-        base.push(path, push);
+        let mut base = authority.generate(self);
+        base.push(path, authority);
         base
     }
 
+    /// Joins `path` to `self`, refusing to leave `self`.
+    ///
+    /// This method is intended for untrusted input. Like [`join`], the result
+    /// is created and modified through `authority`. Unlike [`join`], the
+    /// result is always within `self`:
+    /// - Absolute paths are rejected, including paths that only have a
+    ///   [prefix] or a root on Windows.
+    /// - `..` components are rejected if they would remove a component of
+    ///   `self`, even if later components would return to it, as in
+    ///   `../base/foo`.
+    ///
+    /// `.` components and redundant separators are removed. The file system
+    /// is not accessed, so symlinks within `self` can still point outside of
+    /// it. Use [`resolve_beneath`] to follow them safely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` would leave `self`, or if `authority`
+    /// refuses to remove a component added for `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use normpath::BasePath;
+    /// use normpath::DefaultAuthority;
+    ///
+    /// if !cfg!(windows) {
+    ///     let base = BasePath::try_new("/srv/www").unwrap();
+    ///     let join = |x| base.join_beneath(x, &DefaultAuthority);
+    ///     assert_eq!(
+    ///         Path::new("/srv/www/img/logo.png"),
+    ///         join("css/../img/./logo.png").unwrap(),
+    ///     );
+    ///     assert!(join("../../etc/passwd").is_err());
+    ///     assert!(join("/etc/passwd").is_err());
+    /// }
+    /// ```
+    ///
+    /// [`join`]: Self::join
+    /// [prefix]: ::std::path::Prefix
+    /// [`resolve_beneath`]: Self::resolve_beneath
+    #[inline]
+    pub fn join_beneath<P, A>(
+        &self,
+        path: P,
+        authority: &A,
+    ) -> Result<BasePathBuf, EscapeError>
+    where
+        P: AsRef<Path>,
+        A: Authority + ?Sized,
+    {
+        beneath::join(self, path.as_ref(), authority)
+    }
+
+    /// Equivalent to [`join_beneath`] but resolves symlinks in `path`,
+    /// refusing to leave `self` through them.
+    ///
+    /// Symlinks are replaced by their targets, which are subject to the same
+    /// restrictions as `path`: absolute targets are rejected, as are targets
+    /// with `..` components leaving `self`. Symlinks that are part of `self`
+    /// are not resolved. Components after the first one that does not exist
+    /// are joined without accessing the file system, so the result does not
+    /// need to exist.
+    ///
+    /// # Implementation
+    ///
+    /// Currently, this method calls:
+    /// - [`openat2`] with `RESOLVE_BENEATH` on Linux, if the path exists and
+    ///   the system call is available.
+    /// - [`fs::symlink_metadata`] and [`fs::read_link`] for each component
+    ///   otherwise.
+    ///
+    /// However, the implementation is subject to change. This section is only
+    /// informative.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ResolveError::Escape`] if `path` or a symlink in it would
+    /// leave `self`, and [`ResolveError::Io`] if accessing the file system
+    /// fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::io;
+    /// use std::path::Path;
+    ///
+    /// use normpath::error::ResolveError;
+    /// use normpath::BasePath;
+    /// use normpath::DefaultAuthority;
+    ///
+    /// let base = BasePath::try_new("/srv/www").unwrap();
+    /// match base.resolve_beneath("uploads/file", &DefaultAuthority) {
+    ///     Ok(path) => println!("serving {}", path.as_path().display()),
+    ///     Err(ResolveError::Escape(error)) => eprintln!("refused: {}", error),
+    ///     Err(error) => return Err(error.into()),
+    /// }
+    /// #
+    /// # Ok::<_, io::Error>(())
+    /// ```
+    ///
+    /// [`fs::read_link`]: ::std::fs::read_link
+    /// [`fs::symlink_metadata`]: ::std::fs::symlink_metadata
+    /// [`join_beneath`]: Self::join_beneath
+    /// [`openat2`]: https://man7.org/linux/man-pages/man2/openat2.2.html
+    #[inline]
+    pub fn resolve_beneath<P, A>(
+        &self,
+        path: P,
+        authority: &A,
+    ) -> Result<BasePathBuf, ResolveError>
+    where
+        P: AsRef<Path>,
+        A: Authority + ?Sized,
+    {
+        beneath::resolve(self, path.as_ref(), authority)
+    }
+
     /// Equivalent to [`PathExt::localize_name`].
     #[cfg(feature = "localization")]
     #[cfg_attr(normpath_docs_rs, doc(cfg(feature = "localization")))]
@@ -312,8 +438,6 @@ impl BasePath {
     }
 
     /// Equivalent to [`PathExt::normalize_virtually`].
-    #[cfg(any(doc, windows))]
-    #[cfg_attr(normpath_docs_rs, doc(cfg(windows)))]
     #[inline]
     pub fn normalize_virtually(&self) -> io::Result<BasePathBuf> {
         self.as_path().normalize_virtually()
@@ -408,6 +532,86 @@ impl BasePath {
         self.as_path().read_link()
     }
 
+    /// Returns a relative path that leads from `base` to this path, when
+    /// joined to it.
+    ///
+    /// Components shared by both paths are removed, and each remaining
+    /// component of `base` is replaced by a `..` component. The result is
+    /// empty if the paths are equal. This method is similar to
+    /// [`pathdiff::diff_paths`].
+    ///
+    /// Returns [`None`] if the paths do not have the same [prefix] and root,
+    /// such as when they are on different drives on Windows, or if `base`
+    /// has a `..` component that is not shared with this path, since it
+    /// cannot be reversed.
+    ///
+    /// The file system is not accessed, so both paths should usually be
+    /// [normalized]. Otherwise, use [`relativize_lexically`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use normpath::BasePath;
+    ///
+    /// if cfg!(windows) {
+    ///     let path = BasePath::try_new(r"X:\foo\bar").unwrap();
+    ///     let base = BasePath::try_new(r"X:\foo\baz").unwrap();
+    ///     assert_eq!(
+    ///         Path::new(r"..\bar"),
+    ///         path.relative_to(base).unwrap(),
+    ///     );
+    ///
+    ///     let base = BasePath::try_new(r"Y:\foo").unwrap();
+    ///     assert_eq!(None, path.relative_to(base));
+    /// }
+    /// ```
+    ///
+    /// [normalized]: Self::normalize
+    /// [`pathdiff::diff_paths`]: https://docs.rs/pathdiff/*/pathdiff/fn.diff_paths.html
+    /// [prefix]: ::std::path::Prefix
+    /// [`relativize_lexically`]: Self::relativize_lexically
+    #[inline]
+    #[must_use]
+    pub fn relative_to(&self, base: &Self) -> Option<PathBuf> {
+        lexical::relative(self.as_path(), base.as_path())
+    }
+
+    /// Equivalent to [`relative_to`] but [normalizes lexically] both paths
+    /// first.
+    ///
+    /// Like that method, this one does not access the file system, so
+    /// symlinks followed by `..` components can make the result incorrect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use normpath::BasePath;
+    ///
+    /// if !cfg!(windows) {
+    ///     let path = BasePath::try_new("/srv/./www/../data").unwrap();
+    ///     let base = BasePath::try_new("/srv/www/").unwrap();
+    ///     assert_eq!(
+    ///         Path::new("../data"),
+    ///         path.relativize_lexically(base).unwrap(),
+    ///     );
+    /// }
+    /// ```
+    ///
+    /// [normalizes lexically]: PathExt::normalize_lexically
+    /// [`relative_to`]: Self::relative_to
+    #[inline]
+    #[must_use]
+    pub fn relativize_lexically(&self, base: &Self) -> Option<PathBuf> {
+        lexical::relative(
+            &lexical::normalize(self.as_path()),
+            &lexical::normalize(base.as_path()),
+        )
+    }
+
     /// Equivalent to [`PathExt::shorten`].
     #[inline]
     pub fn shorten(&self) -> io::Result<Cow<'_, Self>> {
@@ -595,18 +799,22 @@ impl BasePathBuf {
     ///
     /// # use normpath::error::ParentError;
     /// use normpath::BasePathBuf;
+    /// use normpath::DefaultAuthority;
     ///
     /// if cfg!(windows) {
     ///     let mut path = BasePathBuf::try_new(r"X:\foo\bar").unwrap();
-    ///     assert!(path.pop()?);
+    ///     assert!(path.pop(&DefaultAuthority)?);
     ///     assert_eq!(Path::new(r"X:\foo"), path);
     /// }
     /// #
     /// # Ok::<_, ParentError>(())
     /// ```
     #[inline]
-    pub fn pop<F: Fn(&mut PathBuf) -> bool>(&mut self, pop: F) -> Result<bool, ParentError> {
-        self.check_parent().map(|()| self.pop_unchecked(pop))
+    pub fn pop<A>(&mut self, authority: &A) -> Result<bool, ParentError>
+    where
+        A: Authority + ?Sized,
+    {
+        self.check_parent().map(|()| self.pop_unchecked(authority))
     }
 
     /// Equivalent to [`PathBuf::pop`].
@@ -619,18 +827,22 @@ impl BasePathBuf {
     /// use std::path::Path;
     ///
     /// use normpath::BasePathBuf;
+    /// use normpath::DefaultAuthority;
     ///
     /// if cfg!(windows) {
     ///     let mut path = BasePathBuf::try_new(r"X:\foo\..").unwrap();
-    ///     assert!(path.pop_unchecked());
+    ///     assert!(path.pop_unchecked(&DefaultAuthority));
     ///     assert_eq!(Path::new(r"X:\foo"), path);
     /// }
     /// ```
     ///
     /// [`pop`]: Self::pop
     #[inline]
-    pub fn pop_unchecked<P: Fn(&mut PathBuf) -> bool>(&mut self, pop: P) -> bool {
-        pop(&mut self.0)
+    pub fn pop_unchecked<A>(&mut self, authority: &A) -> bool
+    where
+        A: Authority + ?Sized,
+    {
+        authority.pop(&mut self.0)
     }
 
     /// Equivalent to [`BasePath::join`] but modifies `self` in place.
@@ -641,19 +853,21 @@ impl BasePathBuf {
     /// use std::path::Path;
     ///
     /// use normpath::BasePathBuf;
+    /// use normpath::DefaultAuthority;
     ///
     /// if cfg!(windows) {
     ///     let mut path = BasePathBuf::try_new(r"\\?\foo\bar").unwrap();
-    ///     path.push("../baz/test.rs");
+    ///     path.push("../baz/test.rs", &DefaultAuthority);
     ///     assert_eq!(Path::new(r"\\?\foo\baz\test.rs"), path);
     /// }
     /// ```
     #[inline]
-    pub fn push<P, F: Fn(&mut PathBuf, &Path)>(&mut self, path: P, push: F)
+    pub fn push<P, A>(&mut self, path: P, authority: &A)
     where
         P: AsRef<Path>,
+        A: Authority + ?Sized,
     {
-        imp::push(self, path.as_ref(), push);
+        imp::push(self, path.as_ref(), authority);
     }
 }
 
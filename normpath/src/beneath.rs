@@ -0,0 +1,209 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Component;
+use std::path::Path;
+
+use super::error::EscapeError;
+use super::error::ResolveError;
+use super::Authority;
+use super::BasePath;
+use super::BasePathBuf;
+
+// This is the limit used by Linux.
+const MAX_SYMLINKS: usize = 40;
+
+enum Step {
+    Parent,
+    Name(OsString),
+}
+
+// Returns the steps for the components of `path` in reverse order, so that
+// they can be popped.
+fn steps(path: &Path, escaping: &Path) -> Result<Vec<Step>, EscapeError> {
+    let mut steps = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                return Err(EscapeError(escaping.to_owned()));
+            }
+            Component::CurDir => {}
+            Component::ParentDir => steps.push(Step::Parent),
+            Component::Normal(name) => steps.push(Step::Name(name.to_owned())),
+        }
+    }
+    steps.reverse();
+    Ok(steps)
+}
+
+// Joins `path` to `base` one component at a time. If `follow` is true,
+// symlinks are replaced by their targets, except below a component that does
+// not exist.
+fn walk<A>(
+    base: &BasePath,
+    path: &Path,
+    follow: bool,
+    authority: &A,
+) -> Result<BasePathBuf, ResolveError>
+where
+    A: Authority + ?Sized,
+{
+    let mut pending = steps(path, path)?;
+    let mut result = authority.generate(base);
+    // The number of components pushed to `result`.
+    let mut depth = 0_usize;
+    // The depth of the first component that does not exist, if it is still in
+    // `result`.
+    let mut missing = None;
+    let mut symlinks = 0_usize;
+    while let Some(step) = pending.pop() {
+        let name = match step {
+            Step::Parent => {
+                if depth == 0 || !result.pop_unchecked(authority) {
+                    return Err(EscapeError(path.to_owned()).into());
+                }
+                depth -= 1;
+                // Existing components are checked again once `..` leaves the
+                // missing one.
+                if missing.is_some_and(|x| depth < x) {
+                    missing = None;
+                }
+                continue;
+            }
+            Step::Name(name) => name,
+        };
+        result.push(name, authority);
+        depth += 1;
+        if !follow || missing.is_some() {
+            continue;
+        }
+
+        match fs::symlink_metadata(&result) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {}
+            Ok(_) => continue,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                // Components below this one can only be joined.
+                missing = Some(depth);
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        }
+
+        symlinks += 1;
+        if symlinks > MAX_SYMLINKS {
+            return Err(
+                io::Error::other("too many levels of symbolic links").into()
+            );
+        }
+        let target = fs::read_link(&result)?;
+        if !result.pop_unchecked(authority) {
+            return Err(EscapeError(path.to_owned()).into());
+        }
+        depth -= 1;
+        // Absolute targets are rejected, like `RESOLVE_BENEATH` does.
+        pending.extend(steps(&target, path)?);
+    }
+    Ok(result)
+}
+
+pub(crate) fn join<A>(
+    base: &BasePath,
+    path: &Path,
+    authority: &A,
+) -> Result<BasePathBuf, EscapeError>
+where
+    A: Authority + ?Sized,
+{
+    walk(base, path, false, authority).map_err(|x| match x {
+        ResolveError::Escape(error) => error,
+        ResolveError::Io(_) => {
+            unreachable!("the file system was accessed");
+        }
+    })
+}
+
+pub(crate) fn resolve<A>(
+    base: &BasePath,
+    path: &Path,
+    authority: &A,
+) -> Result<BasePathBuf, ResolveError>
+where
+    A: Authority + ?Sized,
+{
+    if path.as_os_str().is_empty() {
+        return Ok(authority.generate(base));
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(result) = linux::resolve(base, path, authority) {
+        return result;
+    }
+    walk(base, path, true, authority)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs;
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use rustix::fs::Mode;
+    use rustix::fs::OFlags;
+    use rustix::fs::ResolveFlags;
+    use rustix::io::Errno;
+
+    use crate::error::EscapeError;
+    use crate::error::ResolveError;
+    use crate::Authority;
+    use crate::BasePath;
+    use crate::BasePathBuf;
+
+    fn fd_path<Fd: AsRawFd>(fd: &Fd) -> Option<PathBuf> {
+        fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).ok()
+    }
+
+    // Returns [None] if the kernel cannot resolve the path, in which case the
+    // resolution should be done in userspace.
+    pub(super) fn resolve<A>(
+        base: &BasePath,
+        path: &Path,
+        authority: &A,
+    ) -> Option<Result<BasePathBuf, ResolveError>>
+    where
+        A: Authority + ?Sized,
+    {
+        let flags = OFlags::PATH | OFlags::CLOEXEC;
+        let dir = rustix::fs::open(
+            base.as_path(),
+            flags | OFlags::DIRECTORY,
+            Mode::empty(),
+        )
+        .ok()?;
+        let file = match rustix::fs::openat2(
+            &dir,
+            path,
+            flags,
+            Mode::empty(),
+            ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+        ) {
+            Ok(file) => file,
+            Err(Errno::XDEV) => {
+                return Some(Err(EscapeError(path.to_owned()).into()));
+            }
+            // The path might not exist yet, or `openat2` might not be
+            // available.
+            Err(Errno::NOENT | Errno::NOSYS | Errno::PERM | Errno::INVAL) => {
+                return None;
+            }
+            Err(error) => return Some(Err(ResolveError::Io(error.into()))),
+        };
+
+        // The kernel returns canonical paths, which are converted to be
+        // relative to `base` as given.
+        let file_path = fd_path(&file)?;
+        let relative = file_path.strip_prefix(fd_path(&dir)?).ok()?;
+        let mut result = authority.generate(base);
+        result.push(relative, authority);
+        Some(Ok(result))
+    }
+}
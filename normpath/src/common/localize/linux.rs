@@ -0,0 +1,233 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str;
+
+// The untranslated names created by "xdg-user-dirs" for each directory.
+const USER_DIRS: &[(&str, &str)] = &[
+    ("XDG_DESKTOP_DIR", "Desktop"),
+    ("XDG_DOCUMENTS_DIR", "Documents"),
+    ("XDG_DOWNLOAD_DIR", "Downloads"),
+    ("XDG_MUSIC_DIR", "Music"),
+    ("XDG_PICTURES_DIR", "Pictures"),
+    ("XDG_PUBLICSHARE_DIR", "Public"),
+    ("XDG_TEMPLATES_DIR", "Templates"),
+    ("XDG_VIDEOS_DIR", "Videos"),
+];
+
+const DEFAULT_DATA_DIRS: &str = "/usr/local/share:/usr/share";
+
+fn var(name: &str) -> Option<OsString> {
+    env::var_os(name).filter(|x| !x.is_empty())
+}
+
+// Returns the locales to try for messages, from most to least specific, as
+// described by the Desktop Entry Specification.
+fn locales() -> Vec<String> {
+    let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|x| env::var(x).ok().filter(|x| !x.is_empty()))
+    else {
+        return Vec::new();
+    };
+    let (locale, modifier) = locale
+        .split_once('@')
+        .map_or((&*locale, None), |(x, y)| (x, Some(y)));
+    let locale = locale.split_once('.').map_or(locale, |(x, _)| x);
+    let (language, country) = locale
+        .split_once('_')
+        .map_or((locale, None), |(x, y)| (x, Some(y)));
+    if matches!(language, "" | "C" | "POSIX") {
+        return Vec::new();
+    }
+
+    let mut locales = Vec::new();
+    if let Some(country) = country {
+        if let Some(modifier) = modifier {
+            locales.push(format!("{}_{}@{}", language, country, modifier));
+        }
+        locales.push(format!("{}_{}", language, country));
+    }
+    if let Some(modifier) = modifier {
+        locales.push(format!("{}@{}", language, modifier));
+    }
+    locales.push(language.to_owned());
+    locales
+}
+
+fn unescape_entry_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            Some(ch) => {
+                result.push('\\');
+                result.push(ch);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+// Reads the "Name" key of a ".directory" desktop entry file in the directory,
+// preferring the most specific locale.
+fn directory_name(path: &Path, locales: &[String]) -> Option<String> {
+    let entry = fs::read_to_string(path.join(".directory")).ok()?;
+
+    let mut in_group = false;
+    // The name and the index of its locale, or the number of locales if it is
+    // not localized.
+    let mut name: Option<(usize, String)> = None;
+    for line in entry.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(group) = line.strip_prefix('[') {
+            in_group = group.trim_end() == "Desktop Entry]";
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let rank = match key.trim_end() {
+            "Name" => locales.len(),
+            key => {
+                let Some(rank) = key
+                    .strip_prefix("Name[")
+                    .and_then(|x| x.strip_suffix(']'))
+                    .and_then(|x| locales.iter().position(|y| x == y))
+                else {
+                    continue;
+                };
+                rank
+            }
+        };
+        if name.as_ref().map_or(true, |&(x, _)| rank < x) {
+            name = Some((rank, unescape_entry_value(value.trim_start())));
+        }
+    }
+    name.map(|(_, x)| x).filter(|x| !x.is_empty())
+}
+
+fn parse_user_dir(value: &str, home: &Path) -> Option<PathBuf> {
+    let value = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut dir = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        dir.push(if ch == '\\' { chars.next()? } else { ch });
+    }
+
+    if let Some(dir) = dir.strip_prefix("$HOME") {
+        return if dir.is_empty() {
+            Some(home.to_owned())
+        } else {
+            dir.strip_prefix('/').map(|x| home.join(x))
+        };
+    }
+    let dir = PathBuf::from(dir);
+    dir.is_absolute().then_some(dir)
+}
+
+// Returns the untranslated name of the XDG user directory at the path, if it
+// is one.
+fn user_dir_name(path: &Path) -> Option<&'static str> {
+    let home = PathBuf::from(var("HOME")?);
+    let config_home = var("XDG_CONFIG_HOME")
+        .map_or_else(|| home.join(".config"), PathBuf::from);
+    let user_dirs =
+        fs::read_to_string(config_home.join("user-dirs.dirs")).ok()?;
+
+    for line in user_dirs.lines() {
+        let Some((key, value)) = line.trim_start().split_once('=') else {
+            continue;
+        };
+        let Some(&(_, name)) = USER_DIRS.iter().find(|&&(x, _)| x == key)
+        else {
+            continue;
+        };
+        // Directories set to the home directory are disabled.
+        if parse_user_dir(value.trim_end(), &home)
+            .is_some_and(|x| x != home && x == path)
+        {
+            return Some(name);
+        }
+    }
+    None
+}
+
+// Finds a message in a GNU gettext message catalog.
+fn find_message<'a>(catalog: &'a [u8], message: &str) -> Option<&'a str> {
+    let from_bytes: fn([u8; 4]) -> u32 =
+        match catalog.get(..4)?.try_into().map(u32::from_le_bytes) {
+            Ok(0x950412DE) => u32::from_le_bytes,
+            Ok(0xDE120495) => u32::from_be_bytes,
+            _ => return None,
+        };
+    let read = |offset: usize| -> Option<usize> {
+        catalog
+            .get(offset..offset.checked_add(4)?)
+            .and_then(|x| x.try_into().ok())
+            .and_then(|x| from_bytes(x).try_into().ok())
+    };
+    let string = |table: usize, index: usize| {
+        let offset = table.checked_add(index.checked_mul(8)?)?;
+        let length = read(offset)?;
+        let start = read(offset.checked_add(4)?)?;
+        catalog.get(start..start.checked_add(length)?)
+    };
+
+    let count = read(8)?;
+    let originals = read(12)?;
+    let translations = read(16)?;
+    for i in 0..count {
+        if string(originals, i)? == message.as_bytes() {
+            return str::from_utf8(string(translations, i)?)
+                .ok()
+                .filter(|x| !x.is_empty());
+        }
+    }
+    None
+}
+
+// Translates a name in the same way as "xdg-user-dirs".
+fn translate_user_dir(name: &str, locales: &[String]) -> Option<String> {
+    let data_dirs =
+        var("XDG_DATA_DIRS").unwrap_or_else(|| DEFAULT_DATA_DIRS.into());
+    for locale in locales {
+        for data_dir in env::split_paths(&data_dirs) {
+            let mut path = data_dir;
+            path.extend(["locale", locale, "LC_MESSAGES", "xdg-user-dirs.mo"]);
+            let Ok(catalog) = fs::read(path) else {
+                continue;
+            };
+            if let Some(name) = find_message(&catalog, name) {
+                return Some(name.to_owned());
+            }
+        }
+    }
+    None
+}
+
+pub(super) fn name(path: &Path) -> Option<String> {
+    let locales = locales();
+    if let Some(name) = directory_name(path, &locales) {
+        return Some(name);
+    }
+    user_dir_name(path).and_then(|x| translate_user_dir(x, &locales))
+}
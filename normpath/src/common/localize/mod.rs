@@ -1,11 +1,14 @@
 use std::ffi::OsString;
 use std::path::Path;
 
+#[cfg(target_os = "linux")]
+mod linux;
+
 #[cfg(any(target_os = "ios", target_os = "macos"))]
 mod macos;
 
 #[cfg_attr(
-    not(any(target_os = "ios", target_os = "macos")),
+    not(any(target_os = "ios", target_os = "linux", target_os = "macos")),
     allow(unused_variables)
 )]
 pub(crate) fn name(path: &Path) -> Option<OsString> {
@@ -14,5 +17,9 @@ pub(crate) fn name(path: &Path) -> Option<OsString> {
     if let Some(path) = path.to_str() {
         return Some(macos::name(path).into());
     }
+    #[cfg(target_os = "linux")]
+    if let Some(name) = linux::name(path) {
+        return Some(name.into());
+    }
     None
 }
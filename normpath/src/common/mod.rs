@@ -1,7 +1,10 @@
 use std::borrow::Cow;
+use std::env;
 use std::io;
 use std::path::Path;
 
+use crate::Authority;
+use crate::BasePath;
 use crate::BasePathBuf;
 
 #[cfg(feature = "localization")]
@@ -23,6 +26,38 @@ pub(crate) fn normalize(path: &Path) -> io::Result<BasePathBuf> {
     path.canonicalize().and_then(BasePathBuf::new)
 }
 
+pub(crate) fn normalize_virtually<A>(
+    path: &Path,
+    authority: &A,
+) -> io::Result<BasePathBuf>
+where
+    A: Authority + ?Sized,
+{
+    // Errors are consistent with [normalize].
+    let bytes = path.as_os_str().as_encoded_bytes();
+    if bytes.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "empty paths cannot be normalized",
+        ));
+    } else if bytes.contains(&0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "paths cannot contain NULs",
+        ));
+    }
+
+    let path = if path.is_absolute() {
+        Cow::Borrowed(path)
+    } else {
+        let current_dir = env::current_dir()?;
+        let base = BasePath::try_new(&current_dir)
+            .expect("the current directory is a base path");
+        Cow::Owned(base.join(path, authority).into_path_buf())
+    };
+    Ok(BasePathBuf(crate::lexical::normalize(&path).into_owned()))
+}
+
 pub(crate) fn expand(path: &Path) -> io::Result<Cow<'_, Path>> {
     path.metadata().map(|_| Cow::Borrowed(path))
 }
@@ -31,8 +66,11 @@ pub(crate) fn shorten(path: &Path) -> io::Result<Cow<'_, Path>> {
     expand(path)
 }
 
-pub(crate) fn push<P: Fn(&mut std::path::PathBuf, &Path)>(base: &mut BasePathBuf, path: &Path, push: P) {
+pub(crate) fn push<A>(base: &mut BasePathBuf, path: &Path, authority: &A)
+where
+    A: Authority + ?Sized,
+{
     if !path.as_os_str().is_empty() {
-        push(&mut base.0, path);
+        authority.push(&mut base.0, path);
     }
-}
\ No newline at end of file
+}
@@ -4,6 +4,7 @@ use std::error::Error;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::io;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -70,3 +71,139 @@ impl Display for ParentError {
 }
 
 impl Error for ParentError {}
+
+/// The error returned when [`PathExt::expand_vars`] fails.
+///
+/// [`PathExt::expand_vars`]: super::PathExt::expand_vars
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ExpandVarsError {
+    /// The lookup function did not define a variable, whose name is given.
+    Undefined(String),
+    /// The path contains an unterminated `${` or a variable reference with an
+    /// invalid name. The path that caused this error is given.
+    InvalidReference(PathBuf),
+    /// The expanded path is missing a prefix on Windows.
+    MissingPrefix(MissingPrefixBufError),
+}
+
+impl Display for ExpandVarsError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undefined(name) => {
+                write!(f, "variable is not defined: \"{}\"", name)
+            }
+            Self::InvalidReference(path) => write!(
+                f,
+                "path has an invalid variable reference: \"{}\"",
+                path.display(),
+            ),
+            Self::MissingPrefix(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for ExpandVarsError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::MissingPrefix(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The error returned when a path would leave the base path it is resolved
+/// beneath.
+///
+/// This error is returned by [`PathExt::normalize_lexically_within`] and
+/// [`BasePath::join_beneath`], and as part of [`ResolveError`].
+///
+/// [`BasePath::join_beneath`]: super::BasePath::join_beneath
+/// [`PathExt::normalize_lexically_within`]: super::PathExt::normalize_lexically_within
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscapeError(pub(super) PathBuf);
+
+impl EscapeError {
+    /// Returns a reference to the path that caused this error.
+    #[inline]
+    #[must_use]
+    pub fn as_path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Returns the path that caused this error.
+    #[inline]
+    #[must_use]
+    pub fn into_path_buf(self) -> PathBuf {
+        self.0
+    }
+}
+
+impl Display for EscapeError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "path escapes its base: \"{}\"", self.0.display())
+    }
+}
+
+impl Error for EscapeError {}
+
+/// The error returned when [`BasePath::resolve_beneath`] fails.
+///
+/// [`BasePath::resolve_beneath`]: super::BasePath::resolve_beneath
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ResolveError {
+    /// The path, or a symlink it contains, would leave the base path.
+    Escape(EscapeError),
+    /// Accessing the file system failed.
+    Io(io::Error),
+}
+
+impl Display for ResolveError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Escape(error) => error.fmt(f),
+            Self::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for ResolveError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Escape(error) => Some(error),
+            Self::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<EscapeError> for ResolveError {
+    #[inline]
+    fn from(value: EscapeError) -> Self {
+        Self::Escape(value)
+    }
+}
+
+impl From<io::Error> for ResolveError {
+    #[inline]
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ResolveError> for io::Error {
+    #[inline]
+    fn from(value: ResolveError) -> Self {
+        match value {
+            ResolveError::Escape(error) => {
+                Self::new(io::ErrorKind::InvalidInput, error)
+            }
+            ResolveError::Io(error) => error,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::error::EscapeError;
+use super::Authority;
+use super::BasePath;
+use super::BasePathBuf;
+
+pub(crate) fn normalize(path: &Path) -> Cow<'_, Path> {
+    let mut components = path.components().peekable();
+    // Verbatim paths should not be modified.
+    if let Some(Component::Prefix(prefix)) = components.peek() {
+        if prefix.kind().is_verbatim() {
+            return Cow::Borrowed(path);
+        }
+    }
+
+    let mut result = PathBuf::new();
+    // The number of normal components at the end of the result, which can be
+    // removed by `..` components.
+    let mut normal = 0_usize;
+    for component in components {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                result.push(component);
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if normal > 0 {
+                    let _ = result.pop();
+                    normal -= 1;
+                } else if !result.has_root() {
+                    // Leading `..` components of relative paths are kept, but
+                    // the parent of a root is the root itself.
+                    result.push(component);
+                }
+            }
+            Component::Normal(_) => {
+                result.push(component);
+                normal += 1;
+            }
+        }
+    }
+    if result.as_os_str().is_empty() && !path.as_os_str().is_empty() {
+        result.push(Component::CurDir);
+    }
+
+    if result.as_os_str() == path.as_os_str() {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(result)
+    }
+}
+
+pub(crate) fn normalize_within<A>(
+    path: &Path,
+    base: &BasePath,
+    authority: &A,
+) -> Result<BasePathBuf, EscapeError>
+where
+    A: Authority + ?Sized,
+{
+    let mut joined = authority.generate(base);
+    joined.push(path, authority);
+    let joined = normalize(joined.as_path());
+    // A base of `.` normalizes to `.`, but is no prefix of the relative paths
+    // joined to it, so `.` components are skipped on both sides.
+    let mut components =
+        joined.components().filter(|&x| x != Component::CurDir);
+    let escapes = normalize(base.as_path())
+        .components()
+        .filter(|&x| x != Component::CurDir)
+        .any(|x| components.next() != Some(x))
+        || components.any(|x| x == Component::ParentDir);
+    if escapes {
+        return Err(EscapeError(path.to_owned()));
+    }
+    Ok(BasePathBuf(joined.into_owned()))
+}
+
+pub(crate) fn relative(path: &Path, base: &Path) -> Option<PathBuf> {
+    let mut components = path
+        .components()
+        .filter(|&x| x != Component::CurDir)
+        .peekable();
+    let mut base_components = base
+        .components()
+        .filter(|&x| x != Component::CurDir)
+        .peekable();
+    while let (Some(component), Some(base_component)) =
+        (components.peek(), base_components.peek())
+    {
+        if component != base_component {
+            break;
+        }
+        let _ = components.next();
+        let _ = base_components.next();
+    }
+
+    // Prefixes and roots must be shared, and only normal components of the
+    // base can be reversed.
+    if let Some(Component::Prefix(_) | Component::RootDir) = components.peek()
+    {
+        return None;
+    }
+    let mut result = PathBuf::new();
+    for component in base_components {
+        if !matches!(component, Component::Normal(_)) {
+            return None;
+        }
+        result.push(Component::ParentDir);
+    }
+    result.extend(components);
+    Some(result)
+}
@@ -15,7 +15,9 @@
 //! - [`BasePathBuf::push`] (replaces [`PathBuf::push`])
 //!
 //! Additionally, these methods can be used for other enhancements:
+//! - [`PathExt::expand_vars`]
 //! - [`PathExt::localize_name`]
+//! - [`PathExt::normalize_lexically`]
 //!
 //! # Features
 //!
@@ -64,12 +66,13 @@
 //! use std::path::Path;
 //!
 //! use normpath::BasePathBuf;
+//! use normpath::DefaultAuthority;
 //! use normpath::PathExt;
 //!
 //! fn find_target_dir(path: &Path) -> io::Result<Option<BasePathBuf>> {
 //!     let mut path = path.normalize()?;
 //!     while !path.ends_with("target") {
-//!         match path.pop() {
+//!         match path.pop(&DefaultAuthority) {
 //!             Ok(true) => continue,
 //!             Ok(false) => {}
 //!             Err(_) => {
@@ -103,11 +106,16 @@
 use std::borrow::Cow;
 #[cfg(feature = "localization")]
 use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::io;
 #[cfg(feature = "localization")]
 use std::path::Component;
 use std::path::Path;
 
+mod authority;
+pub use authority::Authority;
+pub use authority::DefaultAuthority;
+
 mod base;
 pub use base::BasePath;
 pub use base::BasePathBuf;
@@ -115,6 +123,8 @@ pub use base::BasePathBuf;
 mod cmp;
 
 pub mod error;
+use error::EscapeError;
+use error::ExpandVarsError;
 
 #[cfg_attr(windows, path = "windows/mod.rs")]
 #[cfg_attr(not(windows), path = "common/mod.rs")]
@@ -122,6 +132,12 @@ mod imp;
 #[cfg(feature = "localization")]
 use imp::localize;
 
+mod beneath;
+
+mod lexical;
+
+mod vars;
+
 /// Additional methods added to [`Path`].
 pub trait PathExt: private::Sealed {
     /// Expands `self` from its short form, if the convention exists for the
@@ -167,6 +183,73 @@ pub trait PathExt: private::Sealed {
     where
         Self: ToOwned;
 
+    /// Expands a leading tilde and variable references in `self`, using
+    /// `lookup` to read variables.
+    ///
+    /// This method is intended for paths read from configuration files. The
+    /// following syntax is recognized on all platforms:
+    /// - `~` as the first component, which is replaced by the value of
+    ///   `HOME`, or `USERPROFILE` on Windows. Forms such as `~user` are not
+    ///   expanded.
+    /// - `$NAME`, where `NAME` contains ASCII letters, digits and underscores
+    ///   and does not start with a digit.
+    /// - `${NAME}`, where `NAME` is not empty and does not contain whitespace
+    ///   or separators.
+    ///
+    /// On Windows, `%NAME%` is also recognized, with the same restrictions
+    /// as `${NAME}`. Elsewhere, percent signs are common in file names, such
+    /// as percent-encoded ones, and are never expanded.
+    ///
+    /// Other uses of `$` and `%` are kept, and values are inserted without
+    /// being expanded themselves. The environment is never read by this
+    /// method; [`env::var_os`] can be passed as `lookup` to use it.
+    ///
+    /// The file system is not accessed, so the result is not normalized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `lookup` does not define a referenced variable, if
+    /// `${` is not terminated or contains an invalid name, or if the result
+    /// is missing a [prefix] on Windows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ffi::OsString;
+    /// use std::path::Path;
+    ///
+    /// # use normpath::error::ExpandVarsError;
+    /// use normpath::PathExt;
+    ///
+    /// if !cfg!(windows) {
+    ///     let lookup = |name: &str| match name {
+    ///         "HOME" => Some(OsString::from("/home/user")),
+    ///         "XDG_DATA_HOME" => Some(OsString::from("/data")),
+    ///         _ => None,
+    ///     };
+    ///     assert_eq!(
+    ///         Path::new("/home/user/cache"),
+    ///         Path::new("~/cache").expand_vars(lookup)?,
+    ///     );
+    ///     assert_eq!(
+    ///         Path::new("/data/foo"),
+    ///         Path::new("${XDG_DATA_HOME}/foo").expand_vars(lookup)?,
+    ///     );
+    ///     assert!(Path::new("$APPDATA/bar").expand_vars(lookup).is_err());
+    /// }
+    /// #
+    /// # Ok::<_, ExpandVarsError>(())
+    /// ```
+    ///
+    /// [`env::var_os`]: ::std::env::var_os
+    /// [prefix]: ::std::path::Prefix
+    fn expand_vars<F>(
+        &self,
+        lookup: F,
+    ) -> Result<BasePathBuf, ExpandVarsError>
+    where
+        F: FnMut(&str) -> Option<OsString>;
+
     /// Returns the localized simple name for this path.
     ///
     /// If the path does not exist or localization is not possible, the last
@@ -188,6 +271,13 @@ pub trait PathExt: private::Sealed {
     ///
     /// </li><li>
     ///
+    /// The `Name` key of a ".directory" [desktop entry] in the directory on
+    /// Linux, or the "xdg-user-dirs" translation for an [XDG user directory].
+    /// The locale is read from `LC_ALL`, `LC_MESSAGES` or `LANG`, and the
+    /// user directories from "user-dirs.dirs" in `XDG_CONFIG_HOME`.
+    ///
+    /// </li><li>
+    ///
     /// [`SHGetFileInfoW`] on Windows.
     ///
     /// <div class="warning">
@@ -224,10 +314,12 @@ pub trait PathExt: private::Sealed {
     /// assert_eq!("test.rs", &*Path::new("/foo/bar/test.rs").localize_name());
     /// ```
     ///
+    /// [desktop entry]: https://specifications.freedesktop.org/desktop-entry-spec/latest/
     /// [displayNameAtPath]: https://developer.apple.com/documentation/foundation/nsfilemanager/1409751-displaynameatpath
     /// [normalized]: Self::normalize
     /// [rust-lang/rfcs#845]: https://github.com/rust-lang/rfcs/issues/845
     /// [`SHGetFileInfoW`]: https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-shgetfileinfow
+    /// [XDG user directory]: https://www.freedesktop.org/wiki/Software/xdg-user-dirs/
     #[cfg(feature = "localization")]
     #[cfg_attr(normpath_docs_rs, doc(cfg(feature = "localization")))]
     #[must_use]
@@ -240,9 +332,9 @@ pub trait PathExt: private::Sealed {
     /// operations on the path string to be more reliable.
     ///
     /// This method will access the file system to normalize the path. If the
-    /// path might not exist, [`normalize_virtually`] can be used instead, but
-    /// it is only available on Windows. Other platforms require file system
-    /// access to perform normalization.
+    /// path might not exist, [`normalize_virtually`] can be used instead. It
+    /// does not access the file system, but on Unix, it can give a different
+    /// result when the path contains symlinks.
     ///
     /// # Unix Behavior
     ///
@@ -311,6 +403,22 @@ pub trait PathExt: private::Sealed {
 
     /// Equivalent to [`normalize`] but does not access the file system.
     ///
+    /// # Unix Behavior
+    ///
+    /// On Unix, relative paths are joined to the current directory, and the
+    /// result is normalized with [`normalize_lexically`]. Symlinks are not
+    /// resolved, so a `..` component following a symlink removes the symlink
+    /// instead of moving to the parent of its target, unlike [`normalize`].
+    ///
+    /// # Implementation
+    ///
+    /// Currently, this method calls:
+    /// - [`env::current_dir`] on Unix, for relative paths only.
+    /// - [`GetFullPathNameW`] on Windows.
+    ///
+    /// However, the implementation is subject to change. This section is only
+    /// informative.
+    ///
     /// # Errors
     ///
     /// Returns an error if `self` cannot be normalized or contains a null
@@ -333,11 +441,104 @@ pub trait PathExt: private::Sealed {
     /// # Ok::<_, io::Error>(())
     /// ```
     ///
+    /// [`env::current_dir`]: ::std::env::current_dir
+    /// [`GetFullPathNameW`]: https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-getfullpathnamew
     /// [`normalize`]: Self::normalize
-    #[cfg(any(doc, windows))]
-    #[cfg_attr(normpath_docs_rs, doc(cfg(windows)))]
+    /// [`normalize_lexically`]: Self::normalize_lexically
     fn normalize_virtually(&self) -> io::Result<BasePathBuf>;
 
+    /// Removes `.` and `..` components and redundant separators from `self`,
+    /// without accessing the file system.
+    ///
+    /// Unlike [`normalize_virtually`], relative paths are not made absolute,
+    /// so the current directory is never read. The components of the path
+    /// are handled as follows:
+    /// - `.` components are removed.
+    /// - `..` components remove the preceding normal component. Leading `..`
+    ///   components of a relative path are kept, since there is nothing to
+    ///   remove. A `..` component following a root is removed, since the
+    ///   parent of a root is the root itself.
+    /// - Repeated and trailing separators are removed.
+    ///
+    /// A relative path that normalizes to nothing, such as `a/..`, becomes
+    /// `.`. [Verbatim] paths are returned unchanged.
+    ///
+    /// Symlinks are not resolved, so the result might not refer to the same
+    /// file as `self` when a `..` component follows a symlink.
+    ///
+    /// The standard library has an unstable method with the same name, so
+    /// calling this method with function call syntax avoids a warning.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use normpath::PathExt;
+    ///
+    /// assert_eq!(
+    ///     Path::new("../baz/test.rs"),
+    ///     PathExt::normalize_lexically(Path::new(".././foo/..//bar/../baz/test.rs")),
+    /// );
+    /// if !cfg!(windows) {
+    ///     assert_eq!(Path::new("/"), PathExt::normalize_lexically(Path::new("/../..")));
+    /// }
+    /// ```
+    ///
+    /// [`normalize_virtually`]: Self::normalize_virtually
+    /// [verbatim]: ::std::path::Prefix::is_verbatim
+    #[must_use]
+    fn normalize_lexically(&self) -> Cow<'_, Self>
+    where
+        Self: ToOwned;
+
+    /// Joins `self` to `base` and normalizes the result like
+    /// [`normalize_lexically`], refusing to leave `base`.
+    ///
+    /// `self` can be relative to `base`, or absolute if it is located within
+    /// `base`. `..` components are allowed as long as the result is still
+    /// within `base`, even if they temporarily leave it, as in `../base/foo`.
+    ///
+    /// `self` is joined to `base` through `authority`, as by [`BasePath::join`].
+    /// Since the file system is not accessed, symlinks within `base` can
+    /// still point outside of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the normalized path is not within `base`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use normpath::BasePath;
+    /// use normpath::DefaultAuthority;
+    /// use normpath::PathExt;
+    ///
+    /// if !cfg!(windows) {
+    ///     let base = BasePath::try_new("/srv/www").unwrap();
+    ///     assert_eq!(
+    ///         Path::new("/srv/www/img/logo.png"),
+    ///         Path::new("css/../img/logo.png")
+    ///             .normalize_lexically_within(base, &DefaultAuthority)
+    ///             .unwrap(),
+    ///     );
+    ///     assert!(Path::new("../../etc/passwd")
+    ///         .normalize_lexically_within(base, &DefaultAuthority)
+    ///         .is_err());
+    /// }
+    /// ```
+    ///
+    /// [`normalize_lexically`]: Self::normalize_lexically
+    fn normalize_lexically_within<A>(
+        &self,
+        base: &BasePath,
+        authority: &A,
+    ) -> Result<BasePathBuf, EscapeError>
+    where
+        A: Authority + ?Sized;
+
     /// Shortens `self` from its expanded form, if the convention exists for
     /// the platform.
     ///
@@ -388,6 +589,14 @@ impl PathExt for Path {
         imp::expand(self)
     }
 
+    #[inline]
+    fn expand_vars<F>(&self, lookup: F) -> Result<BasePathBuf, ExpandVarsError>
+    where
+        F: FnMut(&str) -> Option<OsString>,
+    {
+        vars::expand(self, lookup)
+    }
+
     #[cfg(feature = "localization")]
     #[inline]
     fn localize_name(&self) -> Cow<'_, OsStr> {
@@ -411,10 +620,26 @@ impl PathExt for Path {
         imp::normalize(self)
     }
 
-    #[cfg(any(doc, windows))]
     #[inline]
     fn normalize_virtually(&self) -> io::Result<BasePathBuf> {
-        imp::normalize_virtually(self)
+        imp::normalize_virtually(self, &DefaultAuthority)
+    }
+
+    #[inline]
+    fn normalize_lexically(&self) -> Cow<'_, Self> {
+        lexical::normalize(self)
+    }
+
+    #[inline]
+    fn normalize_lexically_within<A>(
+        &self,
+        base: &BasePath,
+        authority: &A,
+    ) -> Result<BasePathBuf, EscapeError>
+    where
+        A: Authority + ?Sized,
+    {
+        lexical::normalize_within(self, base, authority)
     }
 
     #[inline]
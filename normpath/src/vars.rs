@@ -0,0 +1,121 @@
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::is_separator;
+use std::path::Path;
+use std::str;
+
+use super::error::ExpandVarsError;
+use super::BasePathBuf;
+
+#[cfg(windows)]
+const HOME: &str = "USERPROFILE";
+#[cfg(not(windows))]
+const HOME: &str = "HOME";
+
+fn os_str(bytes: &[u8]) -> &OsStr {
+    // SAFETY: The slices are only split next to ASCII characters.
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+// Names in braces or percent signs can contain more characters, such as
+// parentheses in "ProgramFiles(x86)".
+fn parse_name(bytes: &[u8]) -> Option<&str> {
+    if bytes.is_empty()
+        || bytes
+            .iter()
+            .any(|&x| x.is_ascii_whitespace() || is_separator(x.into()))
+    {
+        return None;
+    }
+    str::from_utf8(bytes).ok()
+}
+
+pub(crate) fn expand<F>(
+    path: &Path,
+    mut lookup: F,
+) -> Result<BasePathBuf, ExpandVarsError>
+where
+    F: FnMut(&str) -> Option<OsString>,
+{
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let invalid = || ExpandVarsError::InvalidReference(path.to_owned());
+
+    let mut result = OsString::with_capacity(bytes.len());
+    let mut push_var =
+        |result: &mut OsString, name: &str| -> Result<(), ExpandVarsError> {
+            let value = lookup(name)
+                .ok_or_else(|| ExpandVarsError::Undefined(name.to_owned()))?;
+            result.push(value);
+            Ok(())
+        };
+
+    // The index of the first byte that has not been copied to the result.
+    let mut copied = 0;
+    let mut i = 0;
+    if bytes.first() == Some(&b'~')
+        && bytes.get(1).map_or(true, |&x| is_separator(x.into()))
+    {
+        push_var(&mut result, HOME)?;
+        copied = 1;
+        i = 1;
+    }
+    while let Some(&byte) = bytes.get(i) {
+        let (name, end) = match byte {
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                let start = i + 2;
+                let length = bytes[start..]
+                    .iter()
+                    .position(|&x| x == b'}')
+                    .ok_or_else(invalid)?;
+                let name = parse_name(&bytes[start..start + length])
+                    .ok_or_else(invalid)?;
+                (name, start + length + 1)
+            }
+            b'$' => {
+                let start = i + 1;
+                let length = bytes[start..]
+                    .iter()
+                    .take_while(|&&x| is_name_byte(x))
+                    .count();
+                // Other uses of `$` are not variable references.
+                if length == 0 || bytes[start].is_ascii_digit() {
+                    i += 1;
+                    continue;
+                }
+                let name = str::from_utf8(&bytes[start..start + length])
+                    .expect("name is not ASCII");
+                (name, start + length)
+            }
+            b'%' if cfg!(windows) => {
+                let start = i + 1;
+                // Unmatched percent signs are not variable references.
+                let Some((name, length)) = bytes[start..]
+                    .iter()
+                    .position(|&x| x == b'%')
+                    .and_then(|x| {
+                        parse_name(&bytes[start..start + x]).map(|y| (y, x))
+                    })
+                else {
+                    i += 1;
+                    continue;
+                };
+                (name, start + length + 1)
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        result.push(os_str(&bytes[copied..i]));
+        push_var(&mut result, name)?;
+        copied = end;
+        i = end;
+    }
+    result.push(os_str(&bytes[copied..]));
+
+    BasePathBuf::try_new(result).map_err(ExpandVarsError::MissingPrefix)
+}
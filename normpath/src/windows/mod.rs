@@ -16,8 +16,10 @@ use windows_sys::Win32::Storage::FileSystem::GetFullPathNameW;
 use windows_sys::Win32::Storage::FileSystem::GetLongPathNameW;
 use windows_sys::Win32::Storage::FileSystem::GetShortPathNameW;
 
+use crate::Authority;
 use crate::BasePath;
 use crate::BasePathBuf;
+use crate::DefaultAuthority;
 
 #[cfg(feature = "localization")]
 pub(super) mod localize;
@@ -41,7 +43,7 @@ pub(crate) fn to_base(path: &Path) -> io::Result<BasePathBuf> {
     debug_assert!(is_base(&base));
 
     let mut base = BasePathBuf(base);
-    base.push(path);
+    base.push(path, &DefaultAuthority);
     Ok(base)
 }
 
@@ -138,7 +140,14 @@ fn winapi_path(
     Ok(Cow::Owned(OsString::from_wide(&path).into()))
 }
 
-pub(crate) fn normalize_virtually(path: &Path) -> io::Result<BasePathBuf> {
+// The path is made absolute by the system, so no capabilities are used.
+pub(crate) fn normalize_virtually<A>(
+    path: &Path,
+    _: &A,
+) -> io::Result<BasePathBuf>
+where
+    A: Authority + ?Sized,
+{
     winapi_path(path, |path, buffer, capacity| unsafe {
         GetFullPathNameW(path, capacity, buffer, ptr::null_mut())
     })
@@ -146,7 +155,8 @@ pub(crate) fn normalize_virtually(path: &Path) -> io::Result<BasePathBuf> {
 }
 
 pub(crate) fn normalize(path: &Path) -> io::Result<BasePathBuf> {
-    path.metadata().and_then(|_| normalize_virtually(path))
+    path.metadata()
+        .and_then(|_| normalize_virtually(path, &DefaultAuthority))
 }
 
 pub(crate) fn expand(path: &Path) -> io::Result<Cow<'_, Path>> {
@@ -200,7 +210,10 @@ fn push_separator(base: &mut BasePathBuf) {
     base.0.push("");
 }
 
-pub(crate) fn push(base: &mut BasePathBuf, path: &Path) {
+pub(crate) fn push<A>(base: &mut BasePathBuf, path: &Path, authority: &A)
+where
+    A: Authority + ?Sized,
+{
     let mut components = path.components();
     let mut next_component = components.next();
     match next_component {
@@ -230,7 +243,7 @@ pub(crate) fn push(base: &mut BasePathBuf, path: &Path) {
             while let Some(component) = next_component {
                 match component {
                     Component::CurDir => {}
-                    Component::ParentDir if base.pop().is_ok() => {}
+                    Component::ParentDir if base.pop(authority).is_ok() => {}
                     _ => break,
                 }
                 next_component = components.next();
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use normpath::BasePath;
+use normpath::DefaultAuthority;
+
+#[track_caller]
+fn test_join(path: &str, result: Option<&str>) {
+    let base =
+        BasePath::try_new(if cfg!(windows) { r"X:\ABC" } else { "/tmp" })
+            .unwrap();
+    let joined = base.join_beneath(path, &DefaultAuthority);
+    match result {
+        Some(result) => assert_eq!(Path::new(result), joined.unwrap()),
+        None => assert_eq!(Path::new(path), joined.unwrap_err().as_path()),
+    }
+}
+
+#[test]
+fn test_join_beneath() {
+    if cfg!(windows) {
+        test_join(r"a\.\b", Some(r"X:\ABC\a\b"));
+        test_join(r"a/b\..\c", Some(r"X:\ABC\a\c"));
+        test_join(r"..\ABC\a", None);
+        test_join(r"\a", None);
+        test_join(r"X:a", None);
+        test_join(r"\\server\share\a", None);
+    } else {
+        test_join("", Some("/tmp"));
+        test_join("a/./b", Some("/tmp/a/b"));
+        test_join("a//b/../c/", Some("/tmp/a/c"));
+        test_join("a/..", Some("/tmp"));
+        test_join("a/../..", None);
+        test_join("../tmp/a", None);
+        test_join("/tmp/a", None);
+    }
+}
+
+#[test]
+fn test_join_beneath_authority() {
+    use std::path::PathBuf;
+
+    use normpath::Authority;
+
+    // Refuses to remove components.
+    struct KeepAll;
+
+    impl Authority for KeepAll {
+        fn pop(&self, _base: &mut PathBuf) -> bool {
+            false
+        }
+    }
+
+    let base =
+        BasePath::try_new(if cfg!(windows) { r"X:\ABC" } else { "/tmp" })
+            .unwrap();
+    assert_eq!(
+        base.join("a", &KeepAll),
+        base.join_beneath("a", &KeepAll).unwrap()
+    );
+    assert!(base.join_beneath("a/..", &KeepAll).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_resolve_beneath() -> std::io::Result<()> {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use normpath::error::ResolveError;
+    use normpath::PathExt;
+
+    use tempfile::tempdir;
+
+    let dir = tempdir()?;
+    let base = dir.path().normalize()?;
+    let path = |x: &str| base.join_beneath(x, &DefaultAuthority).unwrap();
+
+    fs::create_dir_all(path("a/b"))?;
+    symlink("a/b", path("inside"))?;
+    symlink("../..", path("a/b/up"))?;
+    symlink("..", path("a/b/parent"))?;
+    symlink("/etc", path("absolute"))?;
+    symlink("loop", path("loop"))?;
+
+    let resolve = |x: &str| base.resolve_beneath(x, &DefaultAuthority);
+    let escapes = |x: &str| {
+        assert!(
+            matches!(resolve(x), Err(ResolveError::Escape(_))),
+            "{:?} does not escape",
+            x,
+        );
+    };
+
+    assert_eq!(base, resolve("")?);
+    assert_eq!(path("a/b"), resolve("inside")?);
+    assert_eq!(path("a"), resolve("inside/parent")?);
+    assert_eq!(base, resolve("inside/up")?);
+    // Components after a missing one are resolved without the kernel.
+    assert_eq!(path("a/b/missing/c"), resolve("inside/missing/./c")?);
+    assert_eq!(path("a"), resolve("inside/missing/../..")?);
+    assert_eq!(path("a/b"), resolve("missing/../inside")?);
+
+    escapes("..");
+    escapes("inside/up/..");
+    escapes("absolute");
+    escapes("absolute/missing");
+    escapes("inside/up/../missing");
+    escapes("missing/../absolute/passwd");
+    escapes("missing/x/../../inside/up/..");
+    assert!(matches!(resolve("loop"), Err(ResolveError::Io(_))));
+
+    Ok(())
+}
@@ -9,6 +9,7 @@ use std::path::Path;
 
 use normpath::BasePath;
 use normpath::BasePathBuf;
+use normpath::DefaultAuthority;
 use normpath::PathExt;
 
 // https://github.com/rust-lang/rust/issues/76483
@@ -57,7 +58,7 @@ pub(crate) fn test(path: &str, joined_path: &str, normalized_path: &str) {
     let base =
         BasePath::try_new(if cfg!(windows) { r"X:\ABC" } else { "/tmp" })
             .unwrap();
-    assert_eq!(joined_path, base.join(path, |p1, p2| p1.push(p2)));
+    assert_eq!(joined_path, base.join(path, &DefaultAuthority));
 
     assert_eq(normalized_path, normalize(joined_path));
     assert_eq(normalized_path, normalize(normalized_path));
@@ -82,6 +83,8 @@ macro_rules! test {
 pub(crate) fn test_join(base: &str, path: &str, result: &str) {
     assert_eq!(
         Path::new(result),
-        BasePath::try_new(base).unwrap().join(path, |p1, p2| p1.push(p2)),
+        BasePath::try_new(base)
+            .unwrap()
+            .join(path, &DefaultAuthority),
     );
 }
@@ -4,6 +4,7 @@ use std::io;
 use std::path::Path;
 
 use normpath::BasePath;
+use normpath::DefaultAuthority;
 use normpath::PathExt;
 
 use tempfile::tempdir;
@@ -32,7 +33,12 @@ fn test_empty() -> io::Result<()> {
     );
 
     let base = env::current_dir()?;
-    assert_eq!(&base, &BasePath::try_new(&base).unwrap().join("", |p1, p2| p1.push(p2)));
+    assert_eq!(
+        &base,
+        &BasePath::try_new(&base)
+            .unwrap()
+            .join("", &DefaultAuthority),
+    );
 
     Ok(())
 }
@@ -45,12 +51,59 @@ fn test_created() -> io::Result<()> {
     let file = dir.as_path().join("foo");
     let _ = File::create(&file)?;
 
-    assert_eq!(file, dir.join("foo", |p1, p2| p1.push(p2)));
+    assert_eq!(file, dir.join("foo", &DefaultAuthority));
     common::assert_eq(&file, file.normalize());
 
     Ok(())
 }
 
+#[test]
+fn test_authority() {
+    use std::cell::Cell;
+    use std::path::PathBuf;
+
+    use normpath::Authority;
+    use normpath::BasePathBuf;
+
+    #[derive(Default)]
+    struct Counter {
+        generated: Cell<usize>,
+        pushed: Cell<usize>,
+        popped: Cell<usize>,
+    }
+
+    impl Authority for Counter {
+        fn generate(&self, base: &BasePath) -> BasePathBuf {
+            self.generated.set(self.generated.get() + 1);
+            base.to_owned()
+        }
+
+        fn push(&self, base: &mut PathBuf, path: &Path) {
+            self.pushed.set(self.pushed.get() + 1);
+            base.push(path);
+        }
+
+        fn pop(&self, _base: &mut PathBuf) -> bool {
+            self.popped.set(self.popped.get() + 1);
+            false
+        }
+    }
+
+    let counter = Counter::default();
+    let base = env::current_dir().unwrap();
+    let mut path = BasePath::try_new(&base).unwrap().join("foo", &counter);
+    assert_eq!(base.join("foo"), path);
+    assert_eq!(Ok(false), path.pop(&counter));
+    assert!(!path.pop_unchecked(&counter as &dyn Authority));
+    path.push("", &counter);
+
+    assert_eq!(1, counter.generated.get());
+    assert_eq!(2, counter.popped.get());
+    if !cfg!(windows) {
+        assert_eq!(1, counter.pushed.get());
+    }
+}
+
 #[cfg(feature = "serde")]
 #[test]
 fn test_serde() -> io::Result<()> {
@@ -0,0 +1,160 @@
+use std::env;
+use std::io;
+use std::path::Path;
+
+use normpath::BasePath;
+use normpath::PathExt;
+
+#[track_caller]
+fn test(path: &str, result: &str) {
+    // [Path::normalize_lexically] is unstable and would conflict.
+    assert_eq!(
+        Path::new(result),
+        PathExt::normalize_lexically(Path::new(path))
+    );
+}
+
+#[cfg(not(windows))]
+#[track_caller]
+fn test_base(base: &str, path: &str, result: Option<&str>) {
+    use normpath::DefaultAuthority;
+
+    let base = BasePath::try_new(base).unwrap();
+    let normalized =
+        Path::new(path).normalize_lexically_within(base, &DefaultAuthority);
+    match result {
+        Some(result) => {
+            assert_eq!(
+                Ok(Path::new(result)),
+                normalized.as_deref().map(AsRef::as_ref)
+            );
+        }
+        None => assert_eq!(Path::new(path), normalized.unwrap_err().as_path()),
+    }
+}
+
+#[test]
+fn test_relative() {
+    test("", "");
+    test(".", ".");
+    test("./.", ".");
+    test("a/..", ".");
+    test("a/b/../c", "a/c");
+    test("a//b/./c/", "a/b/c");
+    test("..", "..");
+    test("../a/../..", "../..");
+    test("./../a", "../a");
+    test("a/../../b", "../b");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_absolute() {
+    test("/", "/");
+    test("//", "/");
+    test("/..", "/");
+    test("/../a/./b/..", "/a");
+    test("/a/b/../../..", "/");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_absolute() {
+    test(r"X:\", r"X:\");
+    test(r"X:\..", r"X:\");
+    test(r"X:/a\b\..\c", r"X:\a\c");
+    test(r"X:..\a", r"X:..\a");
+    test(r"\\server\share\a\..\..", r"\\server\share\");
+    test(r"\\?\X:\a\..", r"\\?\X:\a\..");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_within() {
+    test_base("/srv", "a/../b", Some("/srv/b"));
+    test_base("/srv", "", Some("/srv"));
+    test_base("/srv", "a/../..", None);
+    test_base("/srv", "..", None);
+    test_base("/srv", "../srv/a", Some("/srv/a"));
+    test_base("/srv", "/srv/a", Some("/srv/a"));
+    test_base("/srv", "/etc", None);
+    test_base("/srv/./www/..", "a", Some("/srv/a"));
+
+    test_base("..", "a", Some("../a"));
+    test_base("..", "../a", None);
+
+    test_base(".", "a", Some("a"));
+    test_base(".", "", Some("."));
+    test_base(".", "..", None);
+    test_base("a/..", "b/../c", Some("c"));
+}
+
+#[test]
+fn test_virtually() -> io::Result<()> {
+    let current_dir = env::current_dir()?;
+    assert_eq!(
+        current_dir.join("a"),
+        Path::new("a/b/..").normalize_virtually()?
+    );
+    assert_eq!(current_dir, Path::new(".").normalize_virtually()?);
+    assert_eq!(
+        io::ErrorKind::NotFound,
+        Path::new("").normalize_virtually().unwrap_err().kind(),
+    );
+    Ok(())
+}
+
+#[track_caller]
+fn test_relative_path(
+    path: &str,
+    base: &str,
+    result: Option<&str>,
+    lexical_result: Option<&str>,
+) {
+    let path = BasePath::try_new(path).unwrap();
+    let base = BasePath::try_new(base).unwrap();
+    assert_eq!(result.map(Path::new), path.relative_to(base).as_deref());
+    assert_eq!(
+        lexical_result.map(Path::new),
+        path.relativize_lexically(base).as_deref(),
+    );
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_relative_to() {
+    test_relative_path("/a/b", "/a/b", Some(""), Some(""));
+    test_relative_path("/a/b/c", "/a", Some("b/c"), Some("b/c"));
+    test_relative_path("/a", "/a/b/c", Some("../.."), Some("../.."));
+    test_relative_path("/a/b", "/a/c/d", Some("../../b"), Some("../../b"));
+    test_relative_path("/a/./b", "/a/b/.", Some(""), Some(""));
+    test_relative_path("/", "/a", Some(".."), Some(".."));
+    test_relative_path("/a/../b", "/a", Some("../b"), Some("../b"));
+    test_relative_path("/b", "/a/..", None, Some("b"));
+    test_relative_path("/a", "/../a", None, Some(""));
+
+    test_relative_path("a/b", "a", Some("b"), Some("b"));
+    test_relative_path("a", "b", Some("../a"), Some("../a"));
+    test_relative_path("../a", "b", Some("../../a"), Some("../../a"));
+    test_relative_path("../a", "../b", Some("../a"), Some("../a"));
+    test_relative_path("a", "../b", None, None);
+    test_relative_path("/a", "a", None, None);
+    test_relative_path("a", "/a", None, None);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_relative_to() {
+    test_relative_path(r"X:\a\b", r"x:\a", Some("b"), Some("b"));
+    test_relative_path(r"X:\a", r"X:\b\c", Some(r"..\..\a"), Some(r"..\..\a"));
+    test_relative_path(r"X:\a", r"Y:\a", None, None);
+    test_relative_path(r"X:\a", r"X:a", None, None);
+    test_relative_path(r"X:a\b", r"X:a", Some("b"), Some("b"));
+    test_relative_path(
+        r"\\server\share\a",
+        r"\\server\share",
+        Some("a"),
+        Some("a"),
+    );
+    test_relative_path(r"\\server\share\a", r"\\server\other\a", None, None);
+}
@@ -0,0 +1,109 @@
+#![cfg(all(feature = "localization", target_os = "linux"))]
+
+// This test changes the environment, so it has its own binary: other tests
+// running at the same time could read the environment while it is written.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use normpath::PathExt;
+
+use tempfile::tempdir;
+
+#[test]
+fn test_xdg() -> io::Result<()> {
+    // Creates a GNU gettext message catalog.
+    fn catalog(messages: &[(&str, &str)]) -> Vec<u8> {
+        let count = messages.len() as u32;
+        let mut tables = Vec::new();
+        let mut strings = Vec::new();
+        let mut offset = 28 + 16 * count;
+        let originals = messages.iter().map(|x| x.0);
+        for string in originals.chain(messages.iter().map(|x| x.1)) {
+            let length = string.len() as u32;
+            tables.extend(length.to_le_bytes());
+            tables.extend(offset.to_le_bytes());
+            strings.extend(string.bytes());
+            strings.push(0);
+            offset += length + 1;
+        }
+
+        let mut catalog = Vec::new();
+        for value in [0x950412DE, 0, count, 28, 28 + 8 * count, 0, 0_u32] {
+            catalog.extend(value.to_le_bytes());
+        }
+        catalog.extend(tables);
+        catalog.extend(strings);
+        catalog
+    }
+
+    let dir = tempdir()?;
+    let dir = dir.path();
+    let home = dir.join("home");
+    let config_home = dir.join("config");
+    let data_dir = dir.join("data");
+    let locale_dir = data_dir.join("locale/de/LC_MESSAGES");
+    for dir in [&home, &config_home, &locale_dir] {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        config_home.join("user-dirs.dirs"),
+        "# Comment\n\
+         XDG_DOCUMENTS_DIR=\"$HOME/Documents\"\n\
+         XDG_DOWNLOAD_DIR=\"$HOME/dl\"\n\
+         XDG_MUSIC_DIR=\"$HOME/\"\n\
+         XDG_VIDEOS_DIR=\"/media/Videos\"\n",
+    )?;
+    fs::write(
+        locale_dir.join("xdg-user-dirs.mo"),
+        catalog(&[
+            ("Documents", "Dokumente"),
+            ("Downloads", "Downloads"),
+            ("Music", "Musik"),
+            ("Videos", "Videos"),
+        ]),
+    )?;
+    let projects = home.join("Projects");
+    fs::create_dir(&projects)?;
+    fs::write(
+        projects.join(".directory"),
+        "[Desktop Entry]\n\
+         Icon=folder\n\
+         Name=Projects\\sAll\n\
+         Name[de]=Projekte\n\
+         Name[fr]=Projets\n\
+         \n\
+         [Other]\n\
+         Name[de_DE]=Other\n",
+    )?;
+
+    env::set_var("HOME", &home);
+    env::set_var("XDG_CONFIG_HOME", &config_home);
+    env::set_var("XDG_DATA_DIRS", &data_dir);
+    env::remove_var("LC_ALL");
+    env::remove_var("LC_MESSAGES");
+
+    let test = |result: &str, path: PathBuf| {
+        assert_eq!(result, &*path.localize_name());
+    };
+    env::set_var("LANG", "de_DE.UTF-8");
+    test("Dokumente", home.join("Documents"));
+    test("Downloads", home.join("dl"));
+    test("Music", home.join("Music"));
+    test("Videos", PathBuf::from("/media/Videos"));
+    test("Pictures", home.join("Pictures"));
+    test("Projekte", projects.clone());
+
+    env::set_var("LANG", "C");
+    test("Documents", home.join("Documents"));
+    test("Projects All", projects.clone());
+
+    env::set_var("XDG_CONFIG_HOME", dir.join("missing"));
+    env::set_var("LANG", "de_DE@euro");
+    test("Documents", home.join("Documents"));
+    test("Projekte", projects);
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use normpath::error::ExpandVarsError;
+use normpath::PathExt;
+
+fn lookup(name: &str) -> Option<OsString> {
+    let value = match name {
+        "HOME" | "USERPROFILE" => "/home/user",
+        "XDG_DATA_HOME" => "/data",
+        "APPDATA" => "/app data",
+        "ProgramFiles(x86)" => "/programs",
+        "EMPTY" => "",
+        _ => return None,
+    };
+    Some(value.into())
+}
+
+#[cfg(not(windows))]
+#[track_caller]
+fn test(path: &str, result: &str) {
+    assert_eq!(
+        Ok(Path::new(result)),
+        Path::new(path)
+            .expand_vars(lookup)
+            .as_deref()
+            .map(AsRef::as_ref),
+    );
+}
+
+#[cfg(not(windows))]
+#[track_caller]
+fn test_error(path: &str, error: ExpandVarsError) {
+    assert_eq!(Err(error), Path::new(path).expand_vars(lookup));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_tilde() {
+    test("~", "/home/user");
+    test("~/cache", "/home/user/cache");
+    test("~user/cache", "~user/cache");
+    test("/~/cache", "/~/cache");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_variables() {
+    test("$XDG_DATA_HOME/foo", "/data/foo");
+    test("${XDG_DATA_HOME}/foo", "/data/foo");
+    test("/a$EMPTY/b${EMPTY}c", "/a/bc");
+    test("$HOME$XDG_DATA_HOME", "/home/user/data");
+    test("/$", "/$");
+    test("/$1/$-/$$", "/$1/$-/$$");
+    test("/100%", "/100%");
+    test("/a%%b", "/a%%b");
+    test("/50% of 10%", "/50% of 10%");
+    test("/%a/b%", "/%a/b%");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_percent() {
+    test("%APPDATA%/bar", "%APPDATA%/bar");
+    test("~/%UNDEFINED%", "/home/user/%UNDEFINED%");
+    test("/srv/file%20a%21", "/srv/file%20a%21");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_errors() {
+    test_error(
+        "$UNDEFINED/foo",
+        ExpandVarsError::Undefined("UNDEFINED".into()),
+    );
+    test_error("${HOME", ExpandVarsError::InvalidReference("${HOME".into()));
+    test_error("${}", ExpandVarsError::InvalidReference("${}".into()));
+    test_error("${a/b}", ExpandVarsError::InvalidReference("${a/b}".into()));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_windows() {
+    let lookup = |name: &str| match name {
+        "APPDATA" => Some(OsString::from(r"X:\AppData")),
+        "USERPROFILE" => Some(OsString::from(r"X:\Users\user")),
+        _ => lookup(name),
+    };
+    let expand = |path: &str| Path::new(path).expand_vars(lookup);
+
+    assert_eq!(
+        Path::new(r"X:\AppData\bar"),
+        expand(r"%APPDATA%\bar").unwrap()
+    );
+    assert_eq!(
+        Path::new(r"X:\Users\user\cache"),
+        expand(r"~\cache").unwrap()
+    );
+    assert_eq!(
+        Path::new(r"X:\programs\foo"),
+        expand(r"X:%ProgramFiles(x86)%\foo").unwrap()
+    );
+    assert_eq!(Path::new(r"X:\100%"), expand(r"X:\100%").unwrap());
+    assert!(matches!(
+        expand(r"%UNDEFINED%\foo"),
+        Err(ExpandVarsError::Undefined(_)),
+    ));
+    assert!(matches!(
+        expand(r"%XDG_DATA_HOME%\foo"),
+        Err(ExpandVarsError::MissingPrefix(_)),
+    ));
+}
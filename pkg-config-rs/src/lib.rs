@@ -9,6 +9,10 @@
 //! After running `pkg-config` all appropriate Cargo metadata will be printed on
 //! stdout if the search was successful.
 //!
+//! Alternatively, `.pc` files can be read and resolved by this crate itself,
+//! without running `pkg-config`, by selecting `Backend::Native` with
+//! `Config::backend`. Enabling the `native` feature makes it the default.
+//!
 //! # Environment variables
 //!
 //! A number of environment variables are available to globally configure how
@@ -84,6 +88,8 @@
 
 #![doc(html_root_url = "https://docs.rs/pkg-config/0.3")]
 
+mod native;
+
 use std::collections::HashMap;
 use std::env;
 use std::error;
@@ -116,6 +122,32 @@ pub struct Config {
     env_metadata: bool,
     print_system_libs: bool,
     print_system_cflags: bool,
+    backend: Backend,
+}
+
+/// How packages are found and resolved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Run the `pkg-config` or `pkgconf` command.
+    Command,
+
+    /// Read and resolve `.pc` files without running any command.
+    ///
+    /// This supports variables, `Requires` and `Requires.private` with version
+    /// constraints, `Libs.private` when linking statically, and
+    /// `PKG_CONFIG_SYSROOT_DIR`. Arguments passed with `Config::arg` are
+    /// ignored.
+    Native,
+}
+
+impl Default for Backend {
+    fn default() -> Backend {
+        if cfg!(feature = "native") {
+            Backend::Native
+        } else {
+            Backend::Command
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -176,6 +208,11 @@ pub enum Error {
         output: Output,
     },
 
+    /// The native backend failed to resolve a library.
+    ///
+    /// Contains the name of the library and the cause.
+    Native { name: String, cause: String },
+
     #[doc(hidden)]
     // please don't match on this, we're likely to add more variants over time
     __Nonexhaustive,
@@ -402,6 +439,10 @@ impl fmt::Display for Error {
                 )?;
                 format_output(output, f)
             }
+            Error::Native {
+                ref name,
+                ref cause,
+            } => write!(f, "Failed to resolve the package `{}`: {}", name, cause),
             Error::__Nonexhaustive => panic!(),
         }
     }
@@ -444,8 +485,14 @@ pub fn target_supported() -> bool {
 /// during cross-compilation unless specifically designed to be used
 /// at that time.
 pub fn get_variable(package: &str, variable: &str) -> Result<String, Error> {
-    let arg = format!("--variable={}", variable);
     let cfg = Config::new();
+    if cfg.backend == Backend::Native {
+        return native::variable(&cfg, package, variable).map_err(|cause| Error::Native {
+            name: package.to_owned(),
+            cause,
+        });
+    }
+    let arg = format!("--variable={}", variable);
     let out = cfg.run(package, &[&arg])?;
     Ok(str::from_utf8(&out).unwrap().trim_end().to_owned())
 }
@@ -463,6 +510,7 @@ impl Config {
             print_system_libs: true,
             cargo_metadata: true,
             env_metadata: true,
+            backend: Backend::default(),
         }
     }
 
@@ -548,6 +596,15 @@ impl Config {
         self
     }
 
+    /// Select how packages are found and resolved.
+    ///
+    /// Defaults to `Backend::Native` if the `native` feature is enabled, or
+    /// `Backend::Command` otherwise.
+    pub fn backend(&mut self, backend: Backend) -> &mut Config {
+        self.backend = backend;
+        self
+    }
+
     /// Deprecated in favor fo the `probe` function
     #[doc(hidden)]
     pub fn find<G: Fn(&str) -> PathBuf, J: Fn(&PathBuf, &PathBuf) -> PathBuf>(&self, name: &str, generator: G, join: J) -> Result<Library, String> {
@@ -568,6 +625,16 @@ impl Config {
 
         let mut library = Library::new();
 
+        if self.backend == Backend::Native {
+            let resolved = native::probe(self, name).map_err(|cause| Error::Native {
+                name: name.to_owned(),
+                cause,
+            })?;
+            library.parse_libs_cflags(name, &resolved.flags, self, generator, join);
+            library.version = resolved.version;
+            return Ok(library);
+        }
+
         let output = self
             .run(name, &["--libs", "--cflags"])
             .map_err(|e| match e {
@@ -765,6 +832,7 @@ impl Default for Config {
             print_system_libs: false,
             cargo_metadata: false,
             env_metadata: false,
+            backend: Backend::default(),
         }
     }
 }
@@ -0,0 +1,826 @@
+//! A built-in replacement for the `pkg-config` binary, used by `Backend::Native`.
+//!
+//! `.pc` files are found through the same search path as `pkg-config`, parsed and resolved along
+//! with their dependencies. The result is formatted like the output of `pkg-config`, so that it
+//! can be handled by `Library::parse_libs_cflags`.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::mem;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use crate::Config;
+
+/// Directories searched when `PKG_CONFIG_LIBDIR` is not set, in addition to a multiarch directory
+/// derived from `TARGET`.
+const DEFAULT_SEARCH_PATHS: &[&str] = &[
+    "/usr/local/lib/pkgconfig",
+    "/usr/local/share/pkgconfig",
+    "/usr/lib64/pkgconfig",
+    "/usr/lib/pkgconfig",
+    "/usr/share/pkgconfig",
+];
+
+const DEFAULT_SYSTEM_INCLUDE_PATHS: &str = "/usr/include";
+const DEFAULT_SYSTEM_LIBRARY_PATHS: &str = "/usr/lib:/lib";
+
+/// The output of resolving a package, equivalent to running `pkg-config --libs --cflags` and
+/// `pkg-config --modversion`.
+pub(crate) struct Resolved {
+    pub(crate) flags: Vec<u8>,
+    pub(crate) version: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn parse(operator: &str) -> Option<Comparison> {
+        Some(match operator {
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessEqual,
+            "=" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            ">=" => Comparison::GreaterEqual,
+            ">" => Comparison::Greater,
+            _ => return None,
+        })
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessEqual => "<=",
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "!=",
+            Comparison::GreaterEqual => ">=",
+            Comparison::Greater => ">",
+        }
+    }
+
+    fn matches(self, version: &str, wanted: &str) -> bool {
+        let ordering = compare_versions(version, wanted);
+        match self {
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessEqual => ordering != Ordering::Greater,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+            Comparison::GreaterEqual => ordering != Ordering::Less,
+            Comparison::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Requirement {
+    name: String,
+    constraint: Option<(Comparison, String)>,
+}
+
+#[derive(Debug, Default)]
+struct Package {
+    version: String,
+    variables: HashMap<String, String>,
+    requires: Vec<Requirement>,
+    requires_private: Vec<Requirement>,
+    libs: Vec<String>,
+    libs_private: Vec<String>,
+    cflags: Vec<String>,
+}
+
+/// Compares versions like `pkg-config`, which uses the algorithm of `rpmvercmp`.
+///
+/// Versions are split into runs of digits or letters, and other characters only separate them.
+/// Runs of digits are compared numerically and are newer than runs of letters.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let mut a = a;
+    let mut b = b;
+    loop {
+        a = a.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        b = b.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        if a.is_empty() || b.is_empty() {
+            break;
+        }
+
+        let numeric = a.starts_with(|c: char| c.is_ascii_digit());
+        let segment_len = |s: &str| {
+            s.find(|c: char| {
+                if numeric {
+                    !c.is_ascii_digit()
+                } else {
+                    !c.is_ascii_alphabetic()
+                }
+            })
+            .unwrap_or(s.len())
+        };
+        let (segment_a, rest_a) = a.split_at(segment_len(a));
+        let (segment_b, rest_b) = b.split_at(segment_len(b));
+        if segment_b.is_empty() {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let ordering = if numeric {
+            let segment_a = segment_a.trim_start_matches('0');
+            let segment_b = segment_b.trim_start_matches('0');
+            segment_a
+                .len()
+                .cmp(&segment_b.len())
+                .then_with(|| segment_a.cmp(segment_b))
+        } else {
+            segment_a.cmp(segment_b)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a = rest_a;
+        b = rest_b;
+    }
+
+    // The version with segments remaining is newer.
+    b.is_empty().cmp(&a.is_empty())
+}
+
+/// Joins lines ending with a backslash and removes comments, which start with an unescaped `#`.
+fn logical_lines(contents: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut in_comment = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                lines.push(mem::replace(&mut line, String::new()));
+                in_comment = false;
+            }
+            _ if in_comment => {}
+            '#' => in_comment = true,
+            '\\' => match chars.peek() {
+                Some(&'\n') => {
+                    chars.next();
+                }
+                Some(&'\r') => {
+                    chars.next();
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                }
+                Some(&'#') => {
+                    chars.next();
+                    line.push('#');
+                }
+                _ => line.push(c),
+            },
+            _ => line.push(c),
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Substitutes references to variables defined earlier, written as `${name}`. `$$` is an escaped
+/// dollar sign.
+fn expand_variables(value: &str, variables: &HashMap<String, String>) -> Result<String, String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if rest.starts_with('$') {
+            result.push('$');
+            rest = &rest[1..];
+        } else if rest.starts_with('{') {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable reference in `{}`", value))?;
+            let name = &rest[1..end];
+            match variables.get(name) {
+                Some(value) => result.push_str(value),
+                None => return Err(format!("variable `{}` is not defined", name)),
+            }
+            rest = &rest[end + 1..];
+        } else {
+            result.push('$');
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Splits a `Libs` or `Cflags` field into arguments like a POSIX shell, without any expansion.
+fn split_arguments(value: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(c) => arg.push(c),
+                    None => arg.push('\\'),
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unterminated quote in `{}`", value)),
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') | Some(c @ '$') | Some(c @ '`') => {
+                                arg.push(c)
+                            }
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(format!("unterminated quote in `{}`", value)),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unterminated quote in `{}`", value)),
+                    }
+                }
+            }
+            _ if c.is_whitespace() => {
+                if in_arg {
+                    args.push(mem::replace(&mut arg, String::new()));
+                    in_arg = false;
+                }
+            }
+            _ => {
+                in_arg = true;
+                arg.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Parses a `Requires` field, such as `glib-2.0 >= 2.50, zlib`. Names and constraints can be
+/// separated by commas or whitespace.
+fn parse_requirements(value: &str) -> Result<Vec<Requirement>, String> {
+    fn is_operator(c: char) -> bool {
+        "<>=!".contains(c)
+    }
+
+    fn is_separator(c: char) -> bool {
+        c == ',' || c.is_whitespace()
+    }
+
+    let mut requirements = Vec::new();
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches(is_separator);
+        if rest.is_empty() {
+            break;
+        }
+
+        let end = rest
+            .find(|c: char| is_separator(c) || is_operator(c))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        rest = rest[end..].trim_start();
+
+        let mut constraint = None;
+        let operator_len = rest.find(|c: char| !is_operator(c)).unwrap_or(rest.len());
+        if operator_len > 0 {
+            let operator = &rest[..operator_len];
+            let comparison = Comparison::parse(operator)
+                .ok_or_else(|| format!("unknown operator `{}` in `{}`", operator, value))?;
+            rest = rest[operator_len..].trim_start();
+            let end = rest.find(is_separator).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!(
+                    "missing version after `{}` in `{}`",
+                    operator, value
+                ));
+            }
+            constraint = Some((comparison, rest[..end].to_owned()));
+            rest = &rest[end..];
+        }
+
+        if name.is_empty() {
+            return Err(format!("missing package name in `{}`", value));
+        }
+        requirements.push(Requirement {
+            name: name.to_owned(),
+            constraint,
+        });
+    }
+    Ok(requirements)
+}
+
+fn parse_package(path: &Path, contents: &str, sysroot: Option<&Path>) -> Result<Package, String> {
+    let mut package = Package::default();
+    let file_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    package
+        .variables
+        .insert("pcfiledir".to_owned(), file_dir.display().to_string());
+    package.variables.insert(
+        "pc_sysrootdir".to_owned(),
+        sysroot
+            .unwrap_or_else(|| Path::new("/"))
+            .display()
+            .to_string(),
+    );
+
+    let mut has_version = false;
+    for line in logical_lines(contents) {
+        let line = line.trim();
+        let key_len = line
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(line.len());
+        let (key, rest) = line.split_at(key_len);
+        let rest = rest.trim_start();
+        let is_variable = rest.starts_with('=');
+        if key.is_empty() || !(is_variable || rest.starts_with(':')) {
+            continue;
+        }
+
+        // Like `pkg-config`, variables are substituted as each line is read, so they have to be
+        // defined before they are used.
+        let value = expand_variables(rest[1..].trim(), &package.variables)?;
+        if is_variable {
+            package.variables.insert(key.to_owned(), value);
+            continue;
+        }
+        match key {
+            "Version" => {
+                package.version = value;
+                has_version = true;
+            }
+            "Requires" => package.requires = parse_requirements(&value)?,
+            "Requires.private" => package.requires_private = parse_requirements(&value)?,
+            "Libs" => package.libs = split_arguments(&value)?,
+            "Libs.private" => package.libs_private = split_arguments(&value)?,
+            "Cflags" | "CFlags" => package.cflags = split_arguments(&value)?,
+            // Other fields, such as `Name`, `Description` and `Conflicts`, are not used.
+            _ => {}
+        }
+    }
+
+    if !has_version {
+        return Err(format!("`{}` has no `Version` field", path.display()));
+    }
+    Ok(package)
+}
+
+/// Escapes an argument so that it is kept as one word by `split_flags`.
+fn escape_argument(arg: &str, output: &mut Vec<u8>) {
+    for &b in arg.as_bytes() {
+        match b {
+            b'\\' | b'\t' | b'\n' | b'\r' | b' ' => output.extend_from_slice(&[b'\\', b]),
+            _ => output.push(b),
+        }
+    }
+}
+
+fn is_in_paths(path: &str, paths: &[PathBuf]) -> bool {
+    paths.iter().any(|x| Path::new(path) == x)
+}
+
+struct Resolver<'a> {
+    config: &'a Config,
+    search_paths: Vec<PathBuf>,
+    sysroot: Option<PathBuf>,
+    statik: bool,
+    packages: Vec<(String, Package)>,
+    /// The index of each package in `packages` by name.
+    indices: HashMap<String, usize>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(config: &'a Config, name: &str) -> Resolver<'a> {
+        let mut search_paths = Vec::new();
+        if let Some(paths) = config.targeted_env_var("PKG_CONFIG_PATH") {
+            search_paths.extend(env::split_paths(&paths));
+        }
+        match config.targeted_env_var("PKG_CONFIG_LIBDIR") {
+            Some(paths) => search_paths.extend(env::split_paths(&paths)),
+            None if cfg!(unix) => {
+                // Debian-based systems use a directory named after the target without its vendor,
+                // such as `/usr/lib/x86_64-linux-gnu/pkgconfig`.
+                if let Ok(target) = env::var("TARGET") {
+                    let parts: Vec<_> = target.split('-').collect();
+                    if parts.len() == 4 {
+                        search_paths.push(
+                            format!("/usr/lib/{}-{}-{}/pkgconfig", parts[0], parts[2], parts[3])
+                                .into(),
+                        );
+                    }
+                }
+                search_paths.extend(DEFAULT_SEARCH_PATHS.iter().map(PathBuf::from));
+            }
+            None => {}
+        }
+
+        Resolver {
+            config,
+            search_paths,
+            sysroot: config
+                .targeted_env_var("PKG_CONFIG_SYSROOT_DIR")
+                .map(PathBuf::from)
+                .filter(|x| !x.as_os_str().is_empty()),
+            statik: config.is_static(name),
+            packages: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    fn find_file(&self, name: &str) -> Option<PathBuf> {
+        // Like `pkg-config`, a path to a `.pc` file can be given instead of a name.
+        let path = Path::new(name);
+        if path.extension().map_or(false, |x| x == "pc") && path.is_file() {
+            return Some(path.to_owned());
+        }
+        self.search_paths
+            .iter()
+            .map(|dir| dir.join(format!("{}.pc", name)))
+            .find(|path| path.is_file())
+    }
+
+    /// Loads a package if necessary, returning its index in `self.packages`.
+    fn load(&mut self, name: &str, required_by: Option<&str>) -> Result<usize, String> {
+        if let Some(&index) = self.indices.get(name) {
+            return Ok(index);
+        }
+
+        let path = match self.find_file(name) {
+            Some(path) => path,
+            None => {
+                let mut message = format!("package `{}`", name);
+                if let Some(required_by) = required_by {
+                    message.push_str(&format!(", required by `{}`,", required_by));
+                }
+                let search_paths: Vec<_> = self
+                    .search_paths
+                    .iter()
+                    .map(|x| x.display().to_string())
+                    .collect();
+                message.push_str(&format!(
+                    " was not found in the search path: {}",
+                    search_paths.join(", ")
+                ));
+                return Err(message);
+            }
+        };
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read `{}`: {}", path.display(), e))?;
+        let package = parse_package(&path, &contents, self.sysroot.as_ref().map(|x| &**x))?;
+        self.indices.insert(name.to_owned(), self.packages.len());
+        self.packages.push((name.to_owned(), package));
+        Ok(self.packages.len() - 1)
+    }
+
+    /// Visits a package and its dependencies depth-first, recording each visit and whether the
+    /// libraries of the package are needed. A package that was visited already with the same
+    /// `link` is only checked against the version constraint.
+    fn visit(
+        &mut self,
+        requirement: &Requirement,
+        required_by: Option<&str>,
+        link: bool,
+        stack: &mut Vec<String>,
+        visits: &mut Vec<(usize, bool)>,
+        visited: &mut HashSet<(usize, bool)>,
+    ) -> Result<(), String> {
+        // Cycles are ignored, since their packages will already be included.
+        if stack.contains(&requirement.name) {
+            return Ok(());
+        }
+
+        let index = self.load(&requirement.name, required_by)?;
+        if let Some((comparison, ref wanted)) = requirement.constraint {
+            let version = &self.packages[index].1.version;
+            if !comparison.matches(version, wanted) {
+                let mut message = format!(
+                    "requested `{} {} {}`",
+                    requirement.name,
+                    comparison.as_str(),
+                    wanted
+                );
+                if let Some(required_by) = required_by {
+                    message.push_str(&format!(" for `{}`", required_by));
+                }
+                message.push_str(&format!(
+                    ", but the version of `{}` is {}",
+                    requirement.name, version
+                ));
+                return Err(message);
+            }
+        }
+        if !visited.insert((index, link)) {
+            return Ok(());
+        }
+        visits.push((index, link));
+
+        let requires = self.packages[index].1.requires.clone();
+        let requires_private = self.packages[index].1.requires_private.clone();
+        stack.push(requirement.name.clone());
+        for dependency in &requires {
+            self.visit(
+                dependency,
+                Some(&requirement.name),
+                link,
+                stack,
+                visits,
+                visited,
+            )?;
+        }
+        // Private dependencies only contribute libraries when linking statically, but their flags
+        // are always needed to compile against public headers.
+        for dependency in &requires_private {
+            let link = link && self.statik;
+            self.visit(
+                dependency,
+                Some(&requirement.name),
+                link,
+                stack,
+                visits,
+                visited,
+            )?;
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    /// Appends the packages whose libraries `index` needs to `order`, each one after its own
+    /// dependencies, visiting requirements from last to first. Reversed, `order` lists the
+    /// packages in the order of their last occurrence in the visits, so that each one comes
+    /// before all of its dependencies.
+    fn link_order(&self, index: usize, linked: &mut HashSet<usize>, order: &mut Vec<usize>) {
+        if !linked.insert(index) {
+            return;
+        }
+        let package = &self.packages[index].1;
+        let private: &[Requirement] = if self.statik {
+            &package.requires_private
+        } else {
+            &[]
+        };
+        for dependency in package.requires.iter().chain(private).rev() {
+            if let Some(&dependency) = self.indices.get(&dependency.name) {
+                self.link_order(dependency, linked, order);
+            }
+        }
+        order.push(index);
+    }
+
+    fn check_bounds(&self, name: &str, version: &str) -> Result<(), String> {
+        let bounds = [
+            (
+                &self.config.min_version,
+                Comparison::GreaterEqual,
+                Comparison::Greater,
+            ),
+            (
+                &self.config.max_version,
+                Comparison::LessEqual,
+                Comparison::Less,
+            ),
+        ];
+        for &(bound, included, excluded) in &bounds {
+            let (comparison, wanted) = match *bound {
+                Bound::Included(ref wanted) => (included, wanted),
+                Bound::Excluded(ref wanted) => (excluded, wanted),
+                Bound::Unbounded => continue,
+            };
+            if !comparison.matches(version, wanted) {
+                return Err(format!(
+                    "requested `{} {} {}`, but the version of `{}` is {}",
+                    name,
+                    comparison.as_str(),
+                    wanted,
+                    name,
+                    version
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the sysroot to include and library paths, like `pkg-config` does.
+    fn add_sysroot(&self, arg: &str) -> String {
+        if let Some(ref sysroot) = self.sysroot {
+            if arg.starts_with("-I/") || arg.starts_with("-L/") {
+                let (flag, path) = arg.split_at(2);
+                if !Path::new(path).starts_with(sysroot) {
+                    return format!("{}{}{}", flag, sysroot.display(), path);
+                }
+            }
+        }
+        arg.to_owned()
+    }
+
+    fn resolve(&mut self, name: &str) -> Result<Resolved, String> {
+        let root = Requirement {
+            name: name.to_owned(),
+            constraint: None,
+        };
+        let mut visits = Vec::new();
+        self.visit(
+            &root,
+            None,
+            true,
+            &mut Vec::new(),
+            &mut visits,
+            &mut HashSet::new(),
+        )?;
+        let root_index = visits[0].0;
+        let version = self.packages[root_index].1.version.clone();
+        self.check_bounds(name, &version)?;
+
+        let system_include_paths = self.system_paths(
+            self.config.print_system_cflags,
+            "PKG_CONFIG_SYSTEM_INCLUDE_PATH",
+            DEFAULT_SYSTEM_INCLUDE_PATHS,
+        );
+        let system_library_paths = self.system_paths(
+            self.config.print_system_libs,
+            "PKG_CONFIG_SYSTEM_LIBRARY_PATH",
+            DEFAULT_SYSTEM_LIBRARY_PATHS,
+        );
+
+        // Include paths are deduplicated keeping their first occurrence. Other flags may take a
+        // separate argument, such as `-isystem`, so they are kept, once for each package.
+        let mut cflags: Vec<String> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut include_paths = HashSet::new();
+        for &(index, _) in &visits {
+            if !seen.insert(index) {
+                continue;
+            }
+            for arg in &self.packages[index].1.cflags {
+                if arg.starts_with("-I") && is_in_paths(&arg[2..], &system_include_paths) {
+                    continue;
+                }
+                let arg = self.add_sysroot(arg);
+                if !arg.starts_with("-I") || include_paths.insert(arg.clone()) {
+                    cflags.push(arg);
+                }
+            }
+        }
+
+        // Packages are linked in the order of their last occurrence, so that each one comes after
+        // all of the packages that depend on it.
+        let mut linked = Vec::new();
+        self.link_order(root_index, &mut HashSet::new(), &mut linked);
+        linked.reverse();
+        // Library paths are deduplicated like include paths.
+        let mut libs = Vec::new();
+        let mut library_paths = HashSet::new();
+        for &index in &linked {
+            let package = &self.packages[index].1;
+            let private: &[String] = if self.statik {
+                &package.libs_private
+            } else {
+                &[]
+            };
+            for arg in package.libs.iter().chain(private) {
+                if arg.starts_with("-L") && is_in_paths(&arg[2..], &system_library_paths) {
+                    continue;
+                }
+                let arg = self.add_sysroot(arg);
+                if !arg.starts_with("-L") || library_paths.insert(arg.clone()) {
+                    libs.push(arg);
+                }
+            }
+        }
+
+        let mut flags = Vec::new();
+        for arg in cflags.iter().chain(&libs) {
+            if !flags.is_empty() {
+                flags.push(b' ');
+            }
+            escape_argument(arg, &mut flags);
+        }
+        flags.push(b'\n');
+        Ok(Resolved { flags, version })
+    }
+
+    /// Returns the paths that are removed from flags, or nothing if they are allowed.
+    fn system_paths(&self, allowed: bool, var_name: &str, default: &str) -> Vec<PathBuf> {
+        if allowed {
+            return Vec::new();
+        }
+        match self.config.env_var_os(var_name) {
+            Some(paths) => env::split_paths(&paths).collect(),
+            None => env::split_paths(default).collect(),
+        }
+    }
+}
+
+/// Resolves the package `name` and its dependencies.
+pub(crate) fn probe(config: &Config, name: &str) -> Result<Resolved, String> {
+    Resolver::new(config, name).resolve(name)
+}
+
+/// Returns the value of a variable defined by the package `name`, or an empty string if it is not
+/// defined, like `pkg-config --variable`.
+pub(crate) fn variable(config: &Config, name: &str, variable: &str) -> Result<String, String> {
+    let mut resolver = Resolver::new(config, name);
+    let index = resolver.load(name, None)?;
+    Ok(resolver.packages[index]
+        .1
+        .variables
+        .get(variable)
+        .cloned()
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_comparison() {
+        assert_eq!(compare_versions("1.0", "1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1_0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.010", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("3.10.0.SVN", "3.10"), Ordering::Greater);
+        assert_eq!(compare_versions("3.10.0", "3.10.0.SVN"), Ordering::Less);
+        assert_eq!(compare_versions("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0a", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("2.0b", "2.0a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn requirements() {
+        let requirement = |name: &str, constraint: Option<(Comparison, &str)>| Requirement {
+            name: name.to_owned(),
+            constraint: constraint.map(|(c, v)| (c, v.to_owned())),
+        };
+        assert_eq!(
+            parse_requirements("glib-2.0 >= 2.50, gobject-2.0,zlib  libpng<1.7 x != 2").unwrap(),
+            vec![
+                requirement("glib-2.0", Some((Comparison::GreaterEqual, "2.50"))),
+                requirement("gobject-2.0", None),
+                requirement("zlib", None),
+                requirement("libpng", Some((Comparison::Less, "1.7"))),
+                requirement("x", Some((Comparison::NotEqual, "2"))),
+            ]
+        );
+        assert!(parse_requirements("foo >=").is_err());
+        assert!(parse_requirements("foo => 1").is_err());
+        assert!(parse_requirements(">= 1").is_err());
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            split_arguments(r#"-I"a b" -DX=\"y\ z\" '-L$c' -lm"#).unwrap(),
+            vec!["-Ia b", "-DX=\"y z\"", "-L$c", "-lm"]
+        );
+        assert!(split_arguments("'-Ifoo").is_err());
+    }
+
+    #[test]
+    fn variables_and_comments() {
+        let contents = "prefix=/opt/x # comment\n\
+                        libdir=${prefix}/lib\n\
+                        price=$$5 \\# not a comment\n\
+                        Version: 1.0\n\
+                        Libs: -L${libdir} \\\n  -lx\n";
+        let package = parse_package(Path::new("/pc/x.pc"), contents, None).unwrap();
+        assert_eq!(package.variables["libdir"], "/opt/x/lib");
+        assert_eq!(package.variables["price"], "$5 # not a comment");
+        assert_eq!(package.variables["pcfiledir"], "/pc");
+        assert_eq!(package.libs, vec!["-L/opt/x/lib", "-lx"]);
+
+        let error = parse_package(Path::new("x.pc"), "a=${b}\nVersion: 1", None).unwrap_err();
+        assert_eq!(error, "variable `b` is not defined");
+        assert!(parse_package(Path::new("x.pc"), "Name: x", None).is_err());
+    }
+}
@@ -323,3 +323,167 @@ fn rpath() {
         .ld_args
         .contains(&vec!["-rpath".to_string(), "/usr/local/lib".to_string(),]));
 }
+
+fn find_native(name: &str, statik: bool) -> Result<pkg_config::Library, Error> {
+    pkg_config::Config::new()
+        .backend(pkg_config::Backend::Native)
+        .statik(statik)
+        .probe(name, generate, |p1, p2| p1.join(p2))
+}
+
+#[test]
+fn native_output_ok() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find_native("foo", false).unwrap();
+    assert_eq!(lib.version, "3.10.0.SVN");
+    assert_eq!(
+        lib.libs,
+        ["coregrind-amd64-linux", "vex-amd64-linux", "gcc"]
+    );
+    assert_eq!(lib.link_paths, [PathBuf::from("/usr/lib/valgrind")]);
+    assert_eq!(
+        lib.include_paths,
+        [
+            PathBuf::from("/usr/include/valgrind"),
+            PathBuf::from("/usr/foo")
+        ]
+    );
+}
+
+#[test]
+fn native_escapes() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find_native("escape", false).unwrap();
+    assert!(lib
+        .include_paths
+        .contains(&PathBuf::from("include path with spaces")));
+    assert!(lib
+        .link_paths
+        .contains(&PathBuf::from("link path with spaces")));
+    assert_eq!(
+        lib.defines.get("A"),
+        Some(&Some("\"escaped string' literal\"".to_owned()))
+    );
+    assert_eq!(
+        lib.defines.get("B"),
+        Some(&Some("ESCAPED IDENTIFIER".to_owned()))
+    );
+    assert_eq!(lib.defines.get("FOX"), Some(&Some("🦊".to_owned())));
+}
+
+#[test]
+fn native_requires() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find_native("native", false).unwrap();
+    assert_eq!(lib.version, "1.2.0");
+    assert_eq!(
+        lib.libs,
+        [
+            "native",
+            "coregrind-amd64-linux",
+            "vex-amd64-linux",
+            "gcc",
+            "native-private-deps",
+        ]
+    );
+    assert_eq!(
+        lib.include_paths,
+        [
+            PathBuf::from("/opt/native/include"),
+            PathBuf::from("/usr/include/valgrind"),
+            PathBuf::from("/opt/native-private/include"),
+            PathBuf::from("/usr/foo"),
+        ]
+    );
+    assert_eq!(lib.defines.get("NATIVE_PRIVATE"), Some(&None));
+}
+
+#[test]
+fn native_requires_static() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find_native("native", true).unwrap();
+    assert_eq!(
+        lib.libs,
+        [
+            "native",
+            "m",
+            "coregrind-amd64-linux",
+            "vex-amd64-linux",
+            "gcc",
+            "native-private-deps",
+            "native-private",
+            "z",
+        ]
+    );
+    assert_eq!(
+        lib.link_paths,
+        [
+            PathBuf::from("/opt/native/lib"),
+            PathBuf::from("/usr/lib/valgrind"),
+            PathBuf::from("/opt/native-private/lib"),
+        ]
+    );
+}
+
+#[test]
+fn native_version() {
+    let _g = LOCK.lock();
+    reset();
+    pkg_config::Config::new()
+        .backend(pkg_config::Backend::Native)
+        .range_version("1.0".."1.10")
+        .probe("native", generate, |p1, p2| p1.join(p2))
+        .unwrap();
+    match pkg_config::Config::new()
+        .backend(pkg_config::Backend::Native)
+        .atleast_version("1.10")
+        .probe("native", generate, |p1, p2| p1.join(p2))
+    {
+        Err(Error::Native { name, cause }) => {
+            assert_eq!(name, "native");
+            assert_eq!(
+                cause,
+                "requested `native >= 1.10`, but the version of `native` is 1.2.0"
+            );
+        }
+        x => panic!("Error::Native expected, found `{:?}`", x),
+    }
+    match find_native("native-version", false) {
+        Err(Error::Native { cause, .. }) => assert_eq!(
+            cause,
+            "requested `foo > 3.10.0.SVN` for `native-version`, \
+             but the version of `foo` is 3.10.0.SVN"
+        ),
+        x => panic!("Error::Native expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn native_not_found() {
+    let _g = LOCK.lock();
+    reset();
+    match find_native("missing", false) {
+        Err(Error::Native { name, .. }) => assert_eq!(name, "missing"),
+        x => panic!("Error::Native expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn native_sysroot() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG_SYSROOT_DIR", "/sysroot");
+    let lib = find_native("foo", false).unwrap();
+    assert_eq!(lib.link_paths, [PathBuf::from("/sysroot/usr/lib/valgrind")]);
+    assert_eq!(
+        lib.include_paths,
+        [
+            PathBuf::from("/sysroot/usr/include/valgrind"),
+            PathBuf::from("/usr/foo"),
+        ]
+    );
+}
@@ -1,8 +1,9 @@
 use std::{
-    ffi::OsStr,
+    ffi::{OsStr, OsString},
     fs::File,
     io::{ErrorKind, Result},
     path::Path,
+    sync::Mutex,
 };
 
 #[cfg(windows)]
@@ -12,8 +13,14 @@ use rayon::prelude::*;
 #[cfg(windows)]
 use windows_sys::Win32::Storage::FileSystem::{DELETE, FILE_LIST_DIRECTORY, FILE_READ_ATTRIBUTES};
 
-mod io;
-mod path_components;
+pub(crate) mod deferred;
+#[cfg(all(feature = "fault-injection", not(windows)))]
+pub(crate) mod fault;
+pub(crate) mod io;
+pub(crate) mod options;
+pub(crate) mod path_components;
+#[cfg(not(windows))]
+pub(crate) mod plan;
 
 cfg_if::cfg_if! {
     if #[cfg(windows)] {
@@ -26,18 +33,14 @@ cfg_if::cfg_if! {
 }
 
 impl super::RemoveDir for std::fs::File {
-    fn remove_dir_contents<G: Fn(&OsStr) -> &Path>(&mut self, debug_root: Option<&Path>, generate: G) -> Result<()> {
+    fn remove_dir_contents<G: Fn(&OsStr) -> &Path + Sync>(&mut self, debug_root: Option<&Path>, generate: G) -> Result<()> {
         // thunk over to the free version adding in the os-specific IO trait impl
-        let debug_root = match debug_root {
-            None => PathComponents::Path(generate("".as_ref())),
-            Some(debug_root) => PathComponents::Path(debug_root),
-        };
-        _remove_dir_contents::<OsIo, _>(self, &debug_root, generate)
+        _remove_open_dir_contents(self, debug_root, &Context::new(&RemoveOptions::new(), &OsIo), generate)
     }
 }
 
 /// Entry point for deprecated function
-pub(crate) fn _ensure_empty_dir_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path>(path: P, generate: G) -> Result<()> {
+pub(crate) fn _ensure_empty_dir_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(path: P, ctx: &Context<'_, I>, generate: G) -> Result<()> {
     // This is as TOCTOU safe as we can make it. Attacks via link replacements
     // in interior components of the path is still possible. if the create
     // succeeds, mission accomplished. if the create fails, open the dir
@@ -46,146 +49,477 @@ pub(crate) fn _ensure_empty_dir_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) ->
     match std::fs::create_dir(&path) {
         Err(e) if e.kind() == ErrorKind::AlreadyExists => {
             // Exists and is a dir. Open it
-            let mut existing_dir = I::open_dir(path.as_ref())?;
-            existing_dir.remove_dir_contents(Some(path.as_ref()), generate)
+            let mut existing_dir = ctx.io.open_dir(path.as_ref())?;
+            _remove_open_dir_contents(&mut existing_dir, Some(path.as_ref()), ctx, generate)
         }
         otherwise => otherwise,
     }
 }
 
 // Deprecated entry point
-pub(crate) fn _remove_dir_contents_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path>(path: P, generate: G) -> Result<()> {
-    let mut d = I::open_dir(path.as_ref())?;
-    _remove_dir_contents::<I, _>(&mut d, &PathComponents::Path(path.as_ref()), generate)
+pub(crate) fn _remove_dir_contents_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(path: P, ctx: &Context<'_, I>, generate: G) -> Result<()> {
+    let mut d = ctx.io.open_dir(path.as_ref())?;
+    _remove_dir_contents(&mut d, &PathComponents::Path(path.as_ref()), ctx, generate)
+}
+
+pub(crate) fn _remove_open_dir_contents<I: io::Io, G: Fn(&OsStr) -> &Path + Sync>(
+    d: &mut File,
+    debug_root: Option<&Path>,
+    ctx: &Context<'_, I>,
+    generate: G,
+) -> Result<()> {
+    let debug_root = match debug_root {
+        None => PathComponents::Path(generate("".as_ref())),
+        Some(debug_root) => PathComponents::Path(debug_root),
+    };
+    _remove_dir_contents(d, &debug_root, ctx, generate)
 }
 
 /// exterior lifetime interface to dir removal
-fn _remove_dir_contents<I: io::Io, G: Fn(&OsStr) -> &Path>(d: &mut File, debug_root: &PathComponents<'_>, generate: G) -> Result<()> {
-    let owned_handle = I::duplicate_fd(d)?;
-    remove_dir_contents_recursive::<I, _>(owned_handle, debug_root, &generate)?;
+fn _remove_dir_contents<I: io::Io, G: Fn(&OsStr) -> &Path + Sync>(d: &mut File, debug_root: &PathComponents<'_>, ctx: &Context<'_, I>, generate: G) -> Result<()> {
+    let owned_handle = ctx.io.duplicate_fd(d)?;
+    ctx.set_root(&owned_handle)?;
+    remove_dir_contents_iterative(owned_handle, debug_root, ctx, &generate)?;
     Ok(())
 }
 
 /// deprecated interface
-pub(crate) fn remove_dir_all_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path>(path: P, generate: G) -> Result<()> {
+pub(crate) fn remove_dir_all_path<I: io::Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(path: P, ctx: &Context<'_, I>, generate: G) -> Result<()> {
     let p = path.as_ref();
     // Opportunity 1 for races
-    let d = I::open_dir(p)?;
+    let d = ctx.io.open_dir(p)?;
     let debug_root = PathComponents::Path(if p.has_root() { p } else { generate(".".as_ref()) });
-    remove_dir_contents_recursive::<OsIo, _>(d, &debug_root, &generate)?;
-    // Opportunity 2 for races
-    std::fs::remove_dir(&path)?;
+    ctx.set_root(&d)?;
+    #[cfg(not(windows))]
+    let id = ctx.io.unique_identifier(&d)?;
+    if !remove_dir_contents_iterative(d, &debug_root, ctx, &generate)? {
+        // Other file systems were skipped inside
+        return Ok(());
+    }
+    ctx.check_cancelled()?;
+    // Opportunity 2 for races, narrowed by checking the path still names the
+    // directory that was emptied.
+    #[cfg(not(windows))]
+    if ctx.io.unique_identifier(&ctx.io.open_dir(p)?)? != id {
+        return Err(replaced(&debug_root));
+    }
+    ctx.io.remove_dir(p)?;
     #[cfg(feature = "log")]
     log::trace!("removed {}", &debug_root);
+    ctx.removed(&debug_root, 0);
     Ok(())
 }
 
-use crate::RemoveDir;
-
+use self::options::{replaced, Context, RemoveOptions};
 use self::path_components::PathComponents;
 
-// Core workhorse, heading towards this being able to be tasks.
+/// A directory on the work stack.
+struct Frame<T> {
+    /// `None` while closed to stay within the open directory budget.
+    dir: Option<File>,
+    /// What the walk keeps track of for this directory.
+    data: T,
+}
+
+/// The state of a directory being removed.
+struct Removal {
+    /// Subdirectories still to be removed; `None` until the directory has been
+    /// scanned.
+    subdirs: Option<Vec<OsString>>,
+    /// Whether nothing inside was skipped.
+    complete: bool,
+}
+
+impl Removal {
+    fn new() -> Self {
+        Removal {
+            subdirs: None,
+            complete: true,
+        }
+    }
+}
+
+/// The explicit work stack replacing recursion, so that neither the call stack
+/// nor the number of open directories grow with the depth of the tree. Removal
+/// and [planning](plan) keep different state for each directory, `T`.
+struct Walk<'a, I: io::Io, T> {
+    ctx: &'a Context<'a, I>,
+    stack: Vec<Frame<T>>,
+    /// The identity of each directory on the stack when it was first opened,
+    /// checked whenever it is opened again or removed.
+    #[cfg(not(windows))]
+    ids: Vec<I::UniqueIdentifier>,
+    /// The names of the directories on the stack below the root, so that
+    /// `names[i]` is the name of `stack[i + 1]` in `stack[i]`.
+    names: Vec<OsString>,
+    /// Number of frames with an open `dir`.
+    open: usize,
+    /// All frames in `1..closed_below` are closed.
+    closed_below: usize,
+    max_open: usize,
+}
+
+impl<'a, I: io::Io, T> Walk<'a, I, T> {
+    fn new(ctx: &'a Context<'a, I>, root: File, data: T) -> Result<Self> {
+        Ok(Walk {
+            ctx,
+            #[cfg(not(windows))]
+            ids: vec![ctx.io.unique_identifier(&root)?],
+            stack: vec![Frame {
+                dir: Some(root),
+                data,
+            }],
+            names: Vec::new(),
+            open: 1,
+            closed_below: 1,
+            max_open: ctx.max_open_dirs(),
+        })
+    }
+
+    fn push(&mut self, name: OsString, dir: File, data: T) -> Result<()> {
+        #[cfg(not(windows))]
+        self.ids.push(self.ctx.io.unique_identifier(&dir)?);
+        self.names.push(name);
+        self.stack.push(Frame {
+            dir: Some(dir),
+            data,
+        });
+        self.open += 1;
+        // Close the directories furthest from the top first: they will be
+        // needed last. The root and the top always stay open.
+        while self.open > self.max_open && self.closed_below < self.stack.len() - 1 {
+            if self.stack[self.closed_below].dir.take().is_some() {
+                self.open -= 1;
+            }
+            self.closed_below += 1;
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> (OsString, Frame<T>) {
+        #[cfg(not(windows))]
+        self.ids.pop();
+        let name = self.names.pop().expect("the root is never popped");
+        let frame = self.stack.pop().expect("the root is never popped");
+        if frame.dir.is_some() {
+            self.open -= 1;
+        }
+        self.closed_below = self.closed_below.min(self.stack.len());
+        (name, frame)
+    }
+
+    /// Re-opens the directory of frame `i` if it was closed, relative to its
+    /// parent with the same race resistant open as when it was first opened,
+    /// and checks that it is still the same directory.
+    fn reopen<G: Fn(&OsStr) -> &Path>(&mut self, i: usize, debug_root: &PathComponents<'_>, generate: &G) -> Result<()> {
+        if self.stack[i].dir.is_some() {
+            return Ok(());
+        }
+        let mut open_ancestor = i - 1;
+        while self.stack[open_ancestor].dir.is_none() {
+            open_ancestor -= 1;
+        }
+        for k in open_ancestor + 1..=i {
+            let parent = self.stack[k - 1].dir.as_ref().expect("opened in order");
+            let replaced = || {
+                let names: Vec<&Path> = self.names[..k].iter().map(|n| generate(n)).collect();
+                replaced(&PathComponents::Nested(debug_root, &names))
+            };
+            let dir = match open_entry(self.ctx, parent, generate(&self.names[k - 1]))? {
+                Entry::Dir(dir) => dir,
+                Entry::Other(..) => return Err(replaced()),
+            };
+            #[cfg(not(windows))]
+            if self.ctx.io.unique_identifier(&dir)? != self.ids[k] {
+                return Err(replaced());
+            }
+            self.stack[k].dir = Some(dir);
+            self.open += 1;
+            self.closed_below = self.closed_below.min(k);
+            // Ancestors on the way are only needed to reach `i`.
+            if self.open > self.max_open && k - 1 > 0 && k - 1 > open_ancestor {
+                self.stack[k - 1].dir = None;
+                self.open -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn dir(&self, i: usize) -> &File {
+        self.stack[i].dir.as_ref().expect("reopened before use")
+    }
+}
+
+/// A directory entry, opened without following symlinks.
+enum Entry {
+    Dir(File),
+    /// A non-directory, with its size. Windows deletes through the handle.
+    #[cfg(windows)]
+    Other(u64, File),
+    #[cfg(not(windows))]
+    Other(u64),
+}
+
+#[cfg(windows)]
+fn open_entry<I: io::Io>(ctx: &Context<'_, I>, parent: &File, name: &Path) -> Result<Entry> {
+    // On windows: open the file and then decide what to do with it.
+    let mut opts = fs_at::OpenOptions::default();
+    // Could possibly drop a syscall by dropping FILE_READ_ATTRIBUTES
+    // and trusting read_dir metadata more. OTOH that would introduce a
+    // race :/.
+    opts.desired_access(DELETE | FILE_LIST_DIRECTORY | FILE_READ_ATTRIBUTES);
+    let _ = ctx;
+    let child_file = opts.open_path_at(parent, name)?;
+    let metadata = child_file.metadata()?;
+    if metadata.is_dir() && !metadata.is_symlink() {
+        Ok(Entry::Dir(child_file))
+    } else {
+        let len = if metadata.is_symlink() { 0 } else { metadata.len() };
+        Ok(Entry::Other(len, child_file))
+    }
+}
+
+#[cfg(not(windows))]
+fn open_entry<I: io::Io>(ctx: &Context<'_, I>, parent: &File, name: &Path) -> Result<Entry> {
+    // Otherwise, open the path safely but normally, fstat to see if its
+    // a dir, then either unlink or descend
+    let result = fixing_permissions(ctx, parent, || ctx.io.open_dir_at(parent, name));
+    // The directory itself may be unreadable, too.
+    let result = match result {
+        Err(e) if ctx.fix_permissions() && e.raw_os_error() == Some(libc::EACCES) => {
+            ctx.io
+                .add_permissions_at(parent, name, libc::S_IRWXU)
+                .map_err(|_| e)?;
+            ctx.io.open_dir_at(parent, name)
+        }
+        otherwise => otherwise,
+    };
+    match result {
+        // We expect is_eloop to be the only error
+        Err(e) if !ctx.io.is_eloop(&e) => Err(e),
+        Err(_) => Ok(Entry::Other(0)),
+        Ok(child_file) => {
+            let metadata = child_file.metadata()?;
+            if metadata.is_dir() {
+                Ok(Entry::Dir(child_file))
+            } else {
+                Ok(Entry::Other(metadata.len()))
+            }
+        }
+    }
+}
+
+/// Runs `op` on an entry of `parent`. If that fails with `EACCES` and
+/// [`RemoveOptions::fix_permissions`] is set, adds owner write and search
+/// permission to `parent` and runs `op` once more.
+#[cfg(not(windows))]
+fn fixing_permissions<I: io::Io, T>(ctx: &Context<'_, I>, parent: &File, op: impl Fn() -> Result<T>) -> Result<T> {
+    match op() {
+        Err(e) if ctx.fix_permissions() && e.raw_os_error() == Some(libc::EACCES) => {
+            #[cfg(feature = "log")]
+            log::debug!("adding write and search permission to a directory after: {}", e);
+            ctx.io
+                .add_permissions(parent, libc::S_IWUSR | libc::S_IXUSR)
+                .map_err(|_| e)?;
+            op()
+        }
+        otherwise => otherwise,
+    }
+}
+
+/// Removes a non-directory entry.
+fn remove_other<I: io::Io>(ctx: &Context<'_, I>, parent: &File, name: &Path, entry: Entry, debug_path: &PathComponents<'_>) -> Result<u64> {
+    #[cfg(windows)]
+    let Entry::Other(len, child_file) = entry else { unreachable!() };
+    #[cfg(not(windows))]
+    let Entry::Other(len) = entry else { unreachable!() };
+    #[cfg(feature = "log")]
+    log::trace!("unlink: {}", debug_path);
+    #[cfg(windows)]
+    let result = {
+        let _ = (ctx, parent, name);
+        child_file.delete_by_handle().map_err(|(_f, e)| e)
+    };
+    #[cfg(not(windows))]
+    let result = fixing_permissions(ctx, parent, || ctx.io.unlink_at(parent, name));
+    #[cfg(feature = "log")]
+    let result = result.inspect_err(|_| log::debug!("error removing {}", debug_path));
+    #[cfg(not(feature = "log"))]
+    let _ = debug_path;
+    result?;
+    Ok(len)
+}
+
+/// Removes an emptied directory. `dir` is its handle, if still open, and `id`
+/// its identity when it was emptied.
+fn remove_empty_dir<I: io::Io>(
+    ctx: &Context<'_, I>,
+    parent: &File,
+    name: &Path,
+    dir: Option<File>,
+    #[cfg(not(windows))] id: &I::UniqueIdentifier,
+    debug_path: &PathComponents<'_>,
+) -> Result<()> {
+    #[cfg(feature = "log")]
+    log::trace!("rmdir: {}", debug_path);
+    #[cfg(windows)]
+    let result = {
+        let dir = match dir {
+            Some(dir) => dir,
+            None => match open_entry(ctx, parent, name)? {
+                Entry::Dir(dir) => dir,
+                Entry::Other(..) => return Err(replaced(debug_path)),
+            },
+        };
+        dir.delete_by_handle().map_err(|(_f, e)| e)
+    };
+    #[cfg(not(windows))]
+    let result = {
+        drop(dir);
+        // Don't remove an empty directory that was swapped in meanwhile.
+        if ctx.io.unique_identifier_at(parent, name)? != *id {
+            return Err(replaced(debug_path));
+        }
+        fixing_permissions(ctx, parent, || ctx.io.rmdir_at(parent, name))
+    };
+    #[cfg(feature = "log")]
+    let result = result.inspect_err(|_| log::debug!("error removing {}", debug_path));
+    #[cfg(not(feature = "log"))]
+    let _ = debug_path;
+    result
+}
+
+/// Removes the non-directories in `d` and returns the names of its
+/// subdirectories. Directory scanning and removal of non-directories is what
+/// the `parallel` feature parallelises.
 #[allow(clippy::map_identity)]
-fn remove_dir_contents_recursive<I: io::Io, G: Fn(&OsStr) -> &Path>(
-    mut d: File,
-    debug_root: &PathComponents<'_>,
-    generate: &G
-) -> Result<File> {
+fn scan<I: io::Io, G: Fn(&OsStr) -> &Path + Sync>(
+    d: &mut File,
+    debug_path: &PathComponents<'_>,
+    ctx: &Context<'_, I>,
+    generate: &G,
+) -> Result<Vec<OsString>> {
     #[cfg(feature = "log")]
-    log::trace!("scanning {}", &debug_root);
-    // We take a os level clone of the FD so that there are no lifetime
+    log::trace!("scanning {}", debug_path);
+    // Read the directory through a duplicate so that there are no lifetime
     // concerns. It would *not* be ok to do readdir on one file twice
     // concurrently because of shared kernel state.
-    let dirfd = I::duplicate_fd(&mut d)?;
+    let mut readdir_fd = ctx.io.duplicate_fd(d)?;
+    let d = &*d;
+    let subdirs = Mutex::new(Vec::new());
     cfg_if::cfg_if! {
         if #[cfg(feature = "parallel")] {
-            let iter = fs_at::read_dir(&mut d)?;
+            let iter = ctx.io.read_dir(&mut readdir_fd)?;
             let iter = iter.par_bridge();
         } else {
-            let mut iter = fs_at::read_dir(&mut d)?;
+            let mut iter = ctx.io.read_dir(&mut readdir_fd)?;
         }
     }
 
     iter.try_for_each(|dir_entry| -> Result<()> {
+        ctx.check_cancelled()?;
         let dir_entry = dir_entry?;
         let name = dir_entry.name();
         if name == OsStr::new(".") || name == OsStr::new("..") {
             return Ok(());
         }
-        let dir_path = generate(name);
-        let dir_debug_root = PathComponents::Component(debug_root, dir_path);
-        #[cfg(windows)]
-        {
-            // On windows: open the file and then decide what to do with it.
-            let mut opts = fs_at::OpenOptions::default();
-            // Could possibly drop a syscall by dropping FILE_READ_ATTRIBUTES
-            // and trusting read_dir metadata more. OTOH that would introduce a
-            // race :/.
-            opts.desired_access(DELETE | FILE_LIST_DIRECTORY | FILE_READ_ATTRIBUTES);
-            let mut child_file = opts.open_path_at(&dirfd, name)?;
-            let metadata = child_file.metadata()?;
-            let is_dir = metadata.is_dir();
-            let is_symlink = metadata.is_symlink();
-            if is_dir && !is_symlink {
-                remove_dir_contents_recursive::<I>(
-                    I::duplicate_fd(&mut child_file)?,
-                    &dir_debug_root,
-                )?;
+        let entry_debug_path = PathComponents::Component(debug_path, generate(name));
+        match open_entry(ctx, d, generate(name))? {
+            Entry::Dir(_) => {
+                subdirs
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(name.to_owned());
             }
-            #[cfg(feature = "log")]
-            log::trace!("delete: {}", &dir_debug_root);
-            child_file.delete_by_handle().map_err(|(_f, e)| {
+            entry => {
+                let len = remove_other(ctx, d, generate(name), entry, &entry_debug_path)?;
                 #[cfg(feature = "log")]
-                log::debug!("error removing {}", dir_debug_root);
-                e
-            })?;
+                log::trace!("removed {}", entry_debug_path);
+                ctx.removed(&entry_debug_path, len);
+            }
         }
-        #[cfg(not(windows))]
-        {
-            // Otherwise, open the path safely but normally, fstat to see if its
-            // a dir, then either unlink or recursively delete
-            let mut opts = fs_at::OpenOptions::default();
-            opts.read(true)
-                .write(fs_at::OpenOptionsWriteMode::Write)
-                .follow(false);
-            let child_result = opts.open_dir_at(&dirfd, generate(name));
-            let is_dir = match child_result {
-                // We expect is_eloop to be the only error
-                Err(e) if !I::is_eloop(&e) => return Err(e),
-                Err(_) => false,
-                Ok(child_file) => {
-                    let metadata = child_file.metadata()?;
-                    let is_dir = metadata.is_dir();
-                    if is_dir {
-                        remove_dir_contents_recursive::<I, _>(child_file, &dir_debug_root, generate)?;
-                        #[cfg(feature = "log")]
-                        log::trace!("rmdir: {}", &dir_debug_root);
-                        let opts = fs_at::OpenOptions::default();
-                        opts.rmdir_at(&dirfd, generate(name)).map_err(|e| {
-                            #[cfg(feature = "log")]
-                            log::debug!("error removing {}", dir_debug_root);
-                            e
-                        })?;
+        Ok(())
+    })?;
+    #[cfg(feature = "log")]
+    log::trace!("scanned {}", debug_path);
+    Ok(subdirs.into_inner().unwrap_or_else(|e| e.into_inner()))
+}
+
+// Core workhorse. Returns whether the directory was emptied, which it is not if
+// other file systems were skipped.
+//
+// Directories are processed depth first from an explicit work stack. Each
+// directory is scanned once, removing its non-directories and remembering its
+// subdirectories by name; those are then opened one at a time relative to the
+// directory. At most `max_open_dirs` directories on the stack are kept open:
+// beyond that the ones nearest the root are closed and re-opened relative to
+// their parent when the walk returns to them.
+fn remove_dir_contents_iterative<I: io::Io, G: Fn(&OsStr) -> &Path + Sync>(
+    d: File,
+    debug_root: &PathComponents<'_>,
+    ctx: &Context<'_, I>,
+    generate: &G,
+) -> Result<bool> {
+    let mut walk = Walk::new(ctx, d, Removal::new())?;
+    loop {
+        let top = walk.stack.len() - 1;
+        walk.reopen(top, debug_root, generate)?;
+        let names: Vec<&Path> = walk.names.iter().map(|n| generate(n)).collect();
+        let debug_path = PathComponents::Nested(debug_root, &names);
+
+        if walk.stack[top].data.subdirs.is_none() {
+            let dir = walk.stack[top].dir.as_mut().expect("reopened before use");
+            let subdirs = scan(dir, &debug_path, ctx, generate)?;
+            walk.stack[top].data.subdirs = Some(subdirs);
+        }
+
+        if let Some(name) = walk.stack[top].data.subdirs.as_mut().and_then(|s| s.pop()) {
+            ctx.check_cancelled()?;
+            let child_debug_path = PathComponents::Component(&debug_path, generate(&name));
+            match open_entry(ctx, walk.dir(top), generate(&name))? {
+                Entry::Dir(child) => {
+                    if ctx.skip_file_system(&child, &child_debug_path)? {
+                        walk.stack[top].data.complete = false;
+                    } else {
+                        drop(names);
+                        walk.push(name, child, Removal::new())?;
                     }
-                    is_dir
                 }
-            };
-            if !is_dir {
-                #[cfg(feature = "log")]
-                log::trace!("unlink: {}", &dir_debug_root);
-                opts.unlink_at(&dirfd, generate(name)).map_err(|e| {
-                    #[cfg(feature = "log")]
-                    log::debug!("error removing {}", dir_debug_root);
-                    e
-                })?;
+                // Replaced since the scan: remove it as what it is now.
+                entry => {
+                    let len = remove_other(ctx, walk.dir(top), generate(&name), entry, &child_debug_path)?;
+                    ctx.removed(&child_debug_path, len);
+                }
             }
+            continue;
         }
-        #[cfg(feature = "log")]
-        log::trace!("removed {}", dir_debug_root);
 
-        Ok(())
-    })?;
-    #[cfg(feature = "log")]
-    log::trace!("scanned {}", &debug_root);
-    Ok(dirfd)
+        if top == 0 {
+            return Ok(walk.stack[0].data.complete);
+        }
+        drop(names);
+        #[cfg(not(windows))]
+        let id = walk.ids[top].clone();
+        let (name, frame) = walk.pop();
+        walk.reopen(top - 1, debug_root, generate)?;
+        if !frame.data.complete {
+            walk.stack[top - 1].data.complete = false;
+            continue;
+        }
+        let names: Vec<&Path> = walk.names.iter().map(|n| generate(n)).collect();
+        let parent_debug_path = PathComponents::Nested(debug_root, &names);
+        let debug_path = PathComponents::Component(&parent_debug_path, generate(&name));
+        remove_empty_dir(
+            ctx,
+            walk.dir(top - 1),
+            generate(&name),
+            frame.dir,
+            #[cfg(not(windows))]
+            &id,
+            &debug_path,
+        )?;
+        #[cfg(feature = "log")]
+        log::trace!("removed {}", debug_path);
+        ctx.removed(&debug_path, 0);
+    }
 }
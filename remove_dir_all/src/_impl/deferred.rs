@@ -0,0 +1,298 @@
+//! Removal in the background, after moving the directory out of the way.
+
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::options::{CancellationToken, RemoveOptions};
+
+/// Trash directories are named `<TRASH_PREFIX><pid>.<unique>`.
+const TRASH_PREFIX: &str = ".remove_dir_all-trash.";
+
+/// Trash directories being removed by this process.
+static ACTIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// A handle to a removal started by
+/// [`remove_dir_all_deferred`](crate::remove_dir_all_deferred) or
+/// [`resume_deferred`](crate::resume_deferred), running on a background
+/// thread.
+///
+/// Dropping the handle lets the removal carry on. If the process exits first,
+/// the trash directories are left behind, to be picked up by
+/// [`resume_deferred`](crate::resume_deferred).
+#[derive(Debug)]
+pub struct DeferredRemoval {
+    trash: Vec<PathBuf>,
+    cancel: CancellationToken,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl DeferredRemoval {
+    /// The trash directories being removed.
+    pub fn trash(&self) -> &[PathBuf] {
+        &self.trash
+    }
+
+    /// Returns `true` once the removal has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    /// Stops the removal between entries. What is left of the trash stays in
+    /// place, and [`DeferredRemoval::wait`] returns an error for which
+    /// [`CancellationToken::is_cancellation`] is `true`.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Blocks until the removal has finished, and returns its result.
+    pub fn wait(mut self) -> Result<()> {
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn spawn<G: Fn(&OsStr) -> &Path + Send + Sync + 'static>(
+        options: RemoveOptions,
+        cancel: CancellationToken,
+        trash: Trash,
+        generate: G,
+    ) -> Result<Self> {
+        let paths = trash.0.clone();
+        let thread = std::thread::Builder::new()
+            .name("remove_dir_all".into())
+            .spawn(move || {
+                for path in &trash.0 {
+                    #[cfg(feature = "log")]
+                    log::debug!("removing trash {}", path.display());
+                    options.remove_dir_all(path, &generate)?;
+                }
+                Ok(())
+            })?;
+        Ok(DeferredRemoval {
+            trash: paths,
+            cancel,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Trash directories registered as being removed by this process until
+/// dropped.
+pub(crate) struct Trash(Vec<PathBuf>);
+
+impl Trash {
+    fn new() -> Self {
+        Trash(Vec::new())
+    }
+
+    /// Registers a new trash path in `parent`, and renames the entry `from`
+    /// of `parent` to it. A name that is taken is never replaced: another one
+    /// is tried instead.
+    fn claim(&mut self, parent: &Parent, from: &OsStr) -> Result<()> {
+        loop {
+            let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.subsec_nanos());
+            let name = format!("{}{}.{:x}-{:x}", TRASH_PREFIX, std::process::id(), nanos, sequence);
+            let to = parent.path.join(&name);
+            lock_active().push(to.clone());
+            match parent.rename_noreplace(from, name.as_ref()) {
+                Ok(()) => {
+                    self.0.push(to);
+                    return Ok(());
+                }
+                Err(e) => {
+                    lock_active().retain(|p| *p != to);
+                    if e.kind() != ErrorKind::AlreadyExists {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Trash {
+    fn drop(&mut self) {
+        lock_active().retain(|p| !self.0.contains(p));
+    }
+}
+
+fn lock_active() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A directory that trash directories are created in.
+struct Parent {
+    path: PathBuf,
+    #[cfg(not(windows))]
+    dir: fs::File,
+}
+
+impl Parent {
+    fn open(path: PathBuf) -> Result<Self> {
+        Ok(Parent {
+            #[cfg(not(windows))]
+            dir: fs::File::open(&path)?,
+            path,
+        })
+    }
+
+    /// Renames the entry `from` to `to`, relative to the directory, failing
+    /// with [`ErrorKind::AlreadyExists`] if `to` exists.
+    #[cfg(not(windows))]
+    fn rename_noreplace(&self, from: &OsStr, to: &OsStr) -> Result<()> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt, os::unix::io::AsRawFd};
+
+        use cvt::cvt;
+
+        let fd = self.dir.as_raw_fd();
+        let from = CString::new(from.as_bytes())?;
+        let to = CString::new(to.as_bytes())?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let renamed = cvt(unsafe {
+                libc::syscall(
+                    libc::SYS_renameat2,
+                    fd,
+                    from.as_ptr(),
+                    fd,
+                    to.as_ptr(),
+                    libc::RENAME_NOREPLACE,
+                )
+            });
+            match renamed {
+                // Not supported by the kernel or the file system.
+                Err(e) if matches!(e.raw_os_error(), Some(libc::ENOSYS | libc::EINVAL)) => {}
+                otherwise => return otherwise.map(drop),
+            }
+        }
+        // Without an exclusive rename, a name taken since this check is still
+        // replaced if it is an empty directory.
+        let mut st = std::mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstatat(fd, to.as_ptr(), st.as_mut_ptr(), libc::AT_SYMLINK_NOFOLLOW) } == 0 {
+            return Err(Error::from_raw_os_error(libc::EEXIST));
+        }
+        match cvt(unsafe { libc::renameat(fd, from.as_ptr(), fd, to.as_ptr()) }) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOTEMPTY) => Err(Error::from_raw_os_error(libc::EEXIST)),
+            otherwise => otherwise.map(drop),
+        }
+    }
+
+    #[cfg(windows)]
+    fn rename_noreplace(&self, from: &OsStr, to: &OsStr) -> Result<()> {
+        let to = self.path.join(to);
+        if fs::symlink_metadata(&to).is_ok() {
+            return Err(Error::new(ErrorKind::AlreadyExists, "trash directory exists"));
+        }
+        fs::rename(self.path.join(from), to)
+    }
+}
+
+/// Renames the directory `path` to a new trash directory next to it.
+pub(crate) fn move_to_trash(path: &Path) -> Result<Trash> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path does not name a directory entry"))?;
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    // Only the parent is normalized: that resolves symlinks, and like
+    // `remove_dir_all` a symlink is refused rather than removing the directory
+    // it points to.
+    let parent = normpath::PathExt::normalize(parent)?.into_path_buf();
+    let path = parent.join(name);
+    if !fs::symlink_metadata(&path)?.is_dir() {
+        return Err(Error::new(ErrorKind::InvalidInput, "not a directory"));
+    }
+    let mut trash = Trash::new();
+    trash.claim(&Parent::open(parent)?, name)?;
+    #[cfg(feature = "log")]
+    log::debug!("moved {} to {}", path.display(), trash.0[0].display());
+    Ok(trash)
+}
+
+/// Claims the trash directories in `dir` whose removal was abandoned: those of
+/// processes that no longer exist, and those of this process that are not
+/// being removed. Each is renamed to a new trash directory, so that only one
+/// process resumes its removal.
+pub(crate) fn abandoned_trash(dir: &Path) -> Result<Trash> {
+    let dir = Parent::open(normpath::PathExt::normalize(dir)?.into_path_buf())?;
+    let mut trash = Trash::new();
+    for entry in fs::read_dir(&dir.path)? {
+        let entry = entry?;
+        let pid = match trash_pid(&entry.file_name()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let path = entry.path();
+        let abandoned = if pid == std::process::id() {
+            !lock_active().contains(&path)
+        } else {
+            !process_exists(pid)
+        };
+        if !abandoned || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        match trash.claim(&dir, &entry.file_name()) {
+            // Claimed by someone else in the meantime.
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            otherwise => otherwise?,
+        }
+    }
+    Ok(trash)
+}
+
+/// The process id in the name of a trash directory.
+fn trash_pid(name: &OsStr) -> Option<u32> {
+    let rest = name.to_str()?.strip_prefix(TRASH_PREFIX)?;
+    rest.split('.').next()?.parse().ok()
+}
+
+/// Whether a process with the id `pid` may be running. Errs on the side of
+/// `true`.
+#[cfg(not(windows))]
+fn process_exists(pid: u32) -> bool {
+    use std::convert::TryFrom;
+
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return false,
+    };
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn process_exists(pid: u32) -> bool {
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, ERROR_ACCESS_DENIED, STILL_ACTIVE},
+        System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+    };
+
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if handle.is_null() {
+        return Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED as i32);
+    }
+    let mut code = 0;
+    let result = unsafe { GetExitCodeProcess(handle, &mut code) };
+    unsafe { CloseHandle(handle) };
+    result == 0 || code == STILL_ACTIVE as u32
+}
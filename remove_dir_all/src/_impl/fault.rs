@@ -0,0 +1,249 @@
+//! A backend that injects failures, for testing code built on this crate.
+
+use std::{
+    ffi::{CString, OsStr, OsString},
+    fs::File,
+    io,
+    os::unix::{ffi::OsStrExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use cvt::cvt;
+
+use super::io::{Io, Stat};
+use super::OsIo;
+
+/// The name of the file created by [`Fault::NotEmptyRace`].
+const INTRUDER: &str = "intruder";
+
+/// An operation of a removal that a [`Fault`] can be injected into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FaultOp {
+    /// Opening a directory entry to find out what it is and descend into it,
+    /// or opening the directory being removed.
+    Open,
+    /// Removing a non-directory.
+    Unlink,
+    /// Removing an emptied directory.
+    Rmdir,
+}
+
+/// What happens when a [`FaultyIo`] rule matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with `EACCES`.
+    PermissionDenied,
+    /// Fail with `EBUSY`, as for a mount point or a file in use.
+    Busy,
+    /// Fail with `ELOOP`, as if the entry were a symlink. A directory failing
+    /// to open this way is taken for a symlink and unlinked, which fails.
+    Loop,
+    /// Create a file named `intruder` inside the entry, which must be a
+    /// directory, just before the operation, as a concurrent writer would. The
+    /// operation then goes ahead: removing the directory fails with
+    /// `ENOTEMPTY`.
+    NotEmptyRace,
+    /// Rename the entry aside to `<name>.swapped` and put a symlink to the
+    /// given target in its place just before the operation, which then goes
+    /// ahead on the symlink.
+    SymlinkSwap(PathBuf),
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: OsString,
+    op: FaultOp,
+    fault: Fault,
+    remaining: AtomicUsize,
+}
+
+/// An I/O backend that performs removals like the default one, except that
+/// chosen operations on chosen entries fail or race with a simulated
+/// concurrent process.
+///
+/// Rules match entries by file name, in any directory of the tree, and are
+/// tried in the order they were added. Use it with
+/// [`RemoveOptions::remove_dir_all_with`](crate::RemoveOptions::remove_dir_all_with),
+/// [`RemoveOptions::remove_dir_contents_with`](crate::RemoveOptions::remove_dir_contents_with),
+/// [`RemoveOptions::plan_remove_dir_all_with`](crate::RemoveOptions::plan_remove_dir_all_with)
+/// or [`RemoveOptions::execute_with`](crate::RemoveOptions::execute_with).
+///
+/// ```rust,no_run
+/// use std::path::Path;
+/// use remove_dir_all::{Fault, FaultOp, FaultyIo, RemoveOptions};
+///
+/// let mut io = FaultyIo::new();
+/// io.inject_once("locked", FaultOp::Unlink, Fault::PermissionDenied);
+/// let err = RemoveOptions::new()
+///     .remove_dir_all_with(&io, "./cache/", |s| Path::new(s))
+///     .unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+/// ```
+///
+/// Only available on unix, with the `fault-injection` feature.
+#[derive(Debug, Default)]
+pub struct FaultyIo {
+    rules: Vec<Rule>,
+    injected: AtomicUsize,
+}
+
+impl FaultyIo {
+    /// Creates a backend that injects no faults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Injects `fault` into every `op` on entries named `name`.
+    pub fn inject<N: AsRef<OsStr>>(&mut self, name: N, op: FaultOp, fault: Fault) -> &mut Self {
+        self.inject_times(name, op, fault, usize::MAX)
+    }
+
+    /// Injects `fault` into the first `op` on an entry named `name` only, as
+    /// for a transient failure.
+    pub fn inject_once<N: AsRef<OsStr>>(&mut self, name: N, op: FaultOp, fault: Fault) -> &mut Self {
+        self.inject_times(name, op, fault, 1)
+    }
+
+    fn inject_times<N: AsRef<OsStr>>(&mut self, name: N, op: FaultOp, fault: Fault, times: usize) -> &mut Self {
+        self.rules.push(Rule {
+            name: name.as_ref().to_owned(),
+            op,
+            fault,
+            remaining: AtomicUsize::new(times),
+        });
+        self
+    }
+
+    /// The number of faults injected so far.
+    pub fn injected(&self) -> usize {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    /// Injects the fault of the first matching rule, if any, into `op` on `p`,
+    /// which is relative to `d` if given.
+    fn fault(&self, d: Option<&File>, p: &Path, op: FaultOp) -> io::Result<()> {
+        let name = match p.file_name() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let rule = self.rules.iter().find(|rule| {
+            rule.op == op
+                && rule.name == name
+                && rule
+                    .remaining
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                    .is_ok()
+        });
+        let rule = match rule {
+            Some(rule) => rule,
+            None => return Ok(()),
+        };
+        self.injected.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "log")]
+        log::debug!("injecting {:?} into {:?} of {}", rule.fault, op, p.display());
+        match (&rule.fault, d) {
+            (Fault::PermissionDenied, _) => Err(io::Error::from_raw_os_error(libc::EACCES)),
+            (Fault::Busy, _) => Err(io::Error::from_raw_os_error(libc::EBUSY)),
+            (Fault::Loop, _) => Err(io::Error::from_raw_os_error(libc::ELOOP)),
+            (Fault::NotEmptyRace, None) => File::create(p.join(INTRUDER)).map(drop),
+            (Fault::NotEmptyRace, Some(d)) => {
+                let dir = fs_at::OpenOptions::default().read(true).follow(false).open_dir_at(d, p)?;
+                let mut opts = fs_at::OpenOptions::default();
+                opts.write(fs_at::OpenOptionsWriteMode::Write).create(true);
+                opts.open_at(&dir, INTRUDER).map(drop)
+            }
+            (Fault::SymlinkSwap(target), None) => {
+                std::fs::rename(p, swapped(p))?;
+                std::os::unix::fs::symlink(target, p)
+            }
+            (Fault::SymlinkSwap(target), Some(d)) => {
+                let from = CString::new(p.as_os_str().as_bytes())?;
+                let to = CString::new(swapped(p).as_os_str().as_bytes())?;
+                cvt(unsafe { libc::renameat(d.as_raw_fd(), from.as_ptr(), d.as_raw_fd(), to.as_ptr()) })?;
+                fs_at::OpenOptions::default().symlink_at(d, p, fs_at::LinkEntryType::Dir, target)
+            }
+        }
+    }
+}
+
+/// The name an entry is renamed to by [`Fault::SymlinkSwap`].
+fn swapped(p: &Path) -> PathBuf {
+    let mut name = p.as_os_str().to_owned();
+    name.push(".swapped");
+    name.into()
+}
+
+impl Io for FaultyIo {
+    /// Device and inode number, as for the default backend.
+    type UniqueIdentifier = (u64, u64);
+
+    fn duplicate_fd(&self, f: &mut File) -> io::Result<File> {
+        OsIo.duplicate_fd(f)
+    }
+
+    fn open_dir(&self, p: &Path) -> io::Result<File> {
+        self.fault(None, p, FaultOp::Open)?;
+        OsIo.open_dir(p)
+    }
+
+    fn remove_dir(&self, p: &Path) -> io::Result<()> {
+        self.fault(None, p, FaultOp::Rmdir)?;
+        OsIo.remove_dir(p)
+    }
+
+    fn device(&self, f: &File) -> io::Result<u64> {
+        OsIo.device(f)
+    }
+
+    fn mount_id(&self, f: &File) -> io::Result<Option<u64>> {
+        OsIo.mount_id(f)
+    }
+
+    fn unique_identifier(&self, d: &File) -> io::Result<Self::UniqueIdentifier> {
+        OsIo.unique_identifier(d)
+    }
+
+    fn unique_identifier_at(&self, d: &File, p: &Path) -> io::Result<Self::UniqueIdentifier> {
+        OsIo.unique_identifier_at(d, p)
+    }
+
+    fn is_eloop(&self, e: &io::Error) -> bool {
+        OsIo.is_eloop(e)
+    }
+
+    fn stat(&self, f: &File) -> io::Result<Stat> {
+        OsIo.stat(f)
+    }
+
+    fn stat_at(&self, d: &File, p: &Path) -> io::Result<Stat> {
+        OsIo.stat_at(d, p)
+    }
+
+    fn add_permissions(&self, d: &File, mode: libc::mode_t) -> io::Result<()> {
+        OsIo.add_permissions(d, mode)
+    }
+
+    fn add_permissions_at(&self, d: &File, p: &Path, mode: libc::mode_t) -> io::Result<()> {
+        OsIo.add_permissions_at(d, p, mode)
+    }
+
+    fn open_dir_at(&self, d: &File, p: &Path) -> io::Result<File> {
+        self.fault(Some(d), p, FaultOp::Open)?;
+        OsIo.open_dir_at(d, p)
+    }
+
+    fn read_dir<'d>(&self, d: &'d mut File) -> io::Result<fs_at::ReadDir<'d>> {
+        OsIo.read_dir(d)
+    }
+
+    fn unlink_at(&self, d: &File, p: &Path) -> io::Result<()> {
+        self.fault(Some(d), p, FaultOp::Unlink)?;
+        OsIo.unlink_at(d, p)
+    }
+
+    fn rmdir_at(&self, d: &File, p: &Path) -> io::Result<()> {
+        self.fault(Some(d), p, FaultOp::Rmdir)?;
+        OsIo.rmdir_at(d, p)
+    }
+}
@@ -4,17 +4,97 @@
 use std::fmt::Debug;
 use std::{fs::File, io, path::Path};
 
-pub(crate) trait Io {
+#[cfg(not(windows))]
+use crate::EntryKind;
+
+/// The metadata of a directory entry needed to recognise it again later.
+#[cfg(not(windows))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stat {
+    pub(crate) kind: EntryKind,
+    pub(crate) len: u64,
+    pub(crate) dev: u64,
+    pub(crate) ino: u64,
+}
+
+/// Public so that it can bound the generic entry points, but not nameable
+/// outside the crate: the backends are [`OsIo`](super::OsIo) and, with the
+/// `fault-injection` feature, [`FaultyIo`](crate::FaultyIo).
+pub trait Io: Sync {
     #[cfg(not(windows))]
-    type UniqueIdentifier: PartialEq + Debug;
+    type UniqueIdentifier: Clone + PartialEq + Debug;
+
+    fn duplicate_fd(&self, f: &mut File) -> io::Result<File>;
+
+    fn open_dir(&self, p: &Path) -> io::Result<File>;
 
-    fn duplicate_fd(f: &mut File) -> io::Result<File>;
+    /// Removes the empty directory at `p`.
+    fn remove_dir(&self, p: &Path) -> io::Result<()> {
+        std::fs::remove_dir(p)
+    }
+
+    /// An identifier of the file system (device or volume) `f` is on.
+    fn device(&self, f: &File) -> io::Result<u64>;
+
+    /// An identifier of the mount `f` is on, if the platform reports one. A
+    /// bind mount is on the same device as the directory it mounts, but not on
+    /// the same mount.
+    fn mount_id(&self, _f: &File) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    #[cfg(not(windows))]
+    fn unique_identifier(&self, d: &File) -> io::Result<Self::UniqueIdentifier>;
+
+    /// The identifier of `p` in `d`, not following a trailing symlink.
+    #[cfg(not(windows))]
+    fn unique_identifier_at(&self, d: &File, p: &Path) -> io::Result<Self::UniqueIdentifier>;
+
+    #[cfg(not(windows))]
+    fn is_eloop(&self, e: &io::Error) -> bool;
+
+    /// fstat
+    #[cfg(not(windows))]
+    fn stat(&self, f: &File) -> io::Result<Stat>;
+
+    /// fstatat, not following a trailing symlink
+    #[cfg(not(windows))]
+    fn stat_at(&self, d: &File, p: &Path) -> io::Result<Stat>;
+
+    /// Adds `mode` to the permission bits of the directory `d`.
+    #[cfg(not(windows))]
+    fn add_permissions(&self, d: &File, mode: libc::mode_t) -> io::Result<()>;
+
+    /// Adds `mode` to the permission bits of the directory `p` in `d`, failing
+    /// instead of following a trailing symlink.
+    #[cfg(not(windows))]
+    fn add_permissions_at(&self, d: &File, p: &Path, mode: libc::mode_t) -> io::Result<()>;
+
+    /// Opens the directory `p` in `d`, failing as [`Io::is_eloop`] if it is a
+    /// symlink.
+    #[cfg(not(windows))]
+    fn open_dir_at(&self, d: &File, p: &Path) -> io::Result<File> {
+        let mut opts = fs_at::OpenOptions::default();
+        opts.read(true)
+            .write(fs_at::OpenOptionsWriteMode::Write)
+            .follow(false);
+        opts.open_dir_at(d, p)
+    }
 
-    fn open_dir(p: &Path) -> io::Result<File>;
+    /// Reads the entries of the directory `d`, including `.` and `..`.
+    fn read_dir<'d>(&self, d: &'d mut File) -> io::Result<fs_at::ReadDir<'d>> {
+        fs_at::read_dir(d)
+    }
 
+    /// Removes the non-directory `p` in `d`.
     #[cfg(not(windows))]
-    fn unique_identifier(d: &File) -> io::Result<Self::UniqueIdentifier>;
+    fn unlink_at(&self, d: &File, p: &Path) -> io::Result<()> {
+        fs_at::OpenOptions::default().unlink_at(d, p)
+    }
 
+    /// Removes the empty directory `p` in `d`.
     #[cfg(not(windows))]
-    fn is_eloop(e: &io::Error) -> bool;
+    fn rmdir_at(&self, d: &File, p: &Path) -> io::Result<()> {
+        fs_at::OpenOptions::default().rmdir_at(d, p)
+    }
 }
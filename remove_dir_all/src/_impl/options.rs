@@ -0,0 +1,487 @@
+//! Configurable removal.
+
+use std::{
+    ffi::OsStr,
+    fmt,
+    fs::File,
+    io::{Error, Result},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use super::deferred::{DeferredRemoval, Trash};
+use super::io::Io;
+use super::path_components::PathComponents;
+#[cfg(not(windows))]
+use super::plan::Plan;
+use super::OsIo;
+
+/// A handle used to abort a removal that is in progress.
+///
+/// Clones share their state: cancelling any clone cancels all removals using
+/// any of them. Cancellation is checked between entries, and a cancelled
+/// removal returns an error for which [`CancellationToken::is_cancellation`]
+/// is `true`. Entries removed before that stay removed.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels all removals using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if [`CancellationToken::cancel`] has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if `e` is the error returned by a cancelled removal.
+    pub fn is_cancellation(e: &Error) -> bool {
+        e.get_ref().is_some_and(|e| e.is::<Cancelled>())
+    }
+}
+
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("removal cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Debug)]
+struct OtherFileSystem(String);
+
+impl fmt::Display for OtherFileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is on a different file system", self.0)
+    }
+}
+
+impl std::error::Error for OtherFileSystem {}
+
+#[derive(Debug)]
+struct DirectoryReplaced(String);
+
+impl fmt::Display for DirectoryReplaced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} was replaced by another directory during removal", self.0)
+    }
+}
+
+impl std::error::Error for DirectoryReplaced {}
+
+/// The error for a directory that is no longer the one being removed.
+pub(crate) fn replaced(path: &PathComponents<'_>) -> Error {
+    Error::other(DirectoryReplaced(path.to_string()))
+}
+
+/// A snapshot of the progress of a removal, passed to the callback set with
+/// [`RemoveOptions::progress`].
+#[derive(Debug)]
+pub struct Progress<'a> {
+    entries: u64,
+    bytes: u64,
+    path: &'a PathComponents<'a>,
+}
+
+impl<'a> Progress<'a> {
+    /// The number of entries removed so far, including this one.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// The number of bytes removed so far, including this entry. Directories
+    /// and symlinks count as zero bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The path of the entry that was just removed.
+    pub fn path(&self) -> &PathComponents<'a> {
+        self.path
+    }
+}
+
+type ProgressFn = dyn Fn(&Progress<'_>) + Send + Sync;
+
+const DEFAULT_MAX_OPEN_DIRS: usize = 64;
+
+/// Options and flags which can be used to configure how a directory tree is
+/// removed.
+///
+/// ```rust,no_run
+/// use std::path::Path;
+/// use remove_dir_all::{CancellationToken, RemoveOptions};
+///
+/// let token = CancellationToken::new();
+/// RemoveOptions::new()
+///     .progress(|p| eprintln!("{} entries, {} bytes: {}", p.entries(), p.bytes(), p.path()))
+///     .cancellation_token(token.clone())
+///     .remove_dir_all("./cache/", |s| Path::new(s))?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct RemoveOptions {
+    progress: Option<Arc<ProgressFn>>,
+    cancel: Option<CancellationToken>,
+    one_file_system: bool,
+    skip_other_file_systems: bool,
+    fix_permissions: bool,
+    max_open_dirs: usize,
+}
+
+impl Default for RemoveOptions {
+    fn default() -> Self {
+        RemoveOptions {
+            progress: None,
+            cancel: None,
+            one_file_system: false,
+            skip_other_file_systems: false,
+            fix_permissions: false,
+            max_open_dirs: DEFAULT_MAX_OPEN_DIRS,
+        }
+    }
+}
+
+impl fmt::Debug for RemoveOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoveOptions")
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .field("cancel", &self.cancel)
+            .field("one_file_system", &self.one_file_system)
+            .field("skip_other_file_systems", &self.skip_other_file_systems)
+            .field("fix_permissions", &self.fix_permissions)
+            .field("max_open_dirs", &self.max_open_dirs)
+            .finish()
+    }
+}
+
+impl RemoveOptions {
+    /// Creates options with the default behaviour of [`remove_dir_all`](crate::remove_dir_all).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `f` after every entry is removed.
+    ///
+    /// With the `parallel` feature `f` is called concurrently from several
+    /// threads, and the counts of consecutive calls may not be in order.
+    pub fn progress<F: Fn(&Progress<'_>) + Send + Sync + 'static>(&mut self, f: F) -> &mut Self {
+        self.progress = Some(Arc::new(f));
+        self
+    }
+
+    /// Checks `token` between entries, and stops with an error once it is
+    /// cancelled.
+    pub fn cancellation_token(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Do not descend into directories on a different file system (mount
+    /// points) than the directory being removed.
+    ///
+    /// File systems are compared by device number, like
+    /// `rm --one-file-system`, and on Linux 5.8 and later also by mount ID, so
+    /// that bind mounts of a directory on the same device are recognised too.
+    ///
+    /// Reaching such a directory fails the removal with an error for which
+    /// [`RemoveOptions::is_other_file_system`] is `true`, unless
+    /// [`RemoveOptions::skip_other_file_systems`] is set. This is like
+    /// `rm --one-file-system`.
+    ///
+    /// This is disabled by default.
+    pub fn one_file_system(&mut self, yes: bool) -> &mut Self {
+        self.one_file_system = yes;
+        self
+    }
+
+    /// When [`RemoveOptions::one_file_system`] is set, leave directories on a
+    /// different file system in place instead of failing. Their ancestors are
+    /// left in place too, as they can't be emptied, and the removal succeeds.
+    ///
+    /// This is disabled by default.
+    pub fn skip_other_file_systems(&mut self, yes: bool) -> &mut Self {
+        self.skip_other_file_systems = yes;
+        self
+    }
+
+    /// When removing an entry or opening a directory fails with `EACCES`, add
+    /// owner write and search permission to the directory containing it (and
+    /// owner read, write and search permission to the entry, if it is a
+    /// directory that can't be opened), then try once more.
+    ///
+    /// This removes read-only trees such as Go module caches. Only the
+    /// permissions of directories inside the tree are changed: directories
+    /// can't be hard linked, so the change can't show up elsewhere, and
+    /// symlinks are never followed. The directory being removed must itself
+    /// be readable.
+    ///
+    /// This is disabled by default. It has no effect on Windows, where
+    /// read-only files are always removed.
+    pub fn fix_permissions(&mut self, yes: bool) -> &mut Self {
+        self.fix_permissions = yes;
+        self
+    }
+
+    /// Limits how many directories on the path from the directory being
+    /// removed to the current one are held open at the same time.
+    ///
+    /// Deeper trees are still removed: directories nearest the root are
+    /// closed, and re-opened relative to their parent (never through a path)
+    /// when the removal returns to them. Lower limits trade extra opens for
+    /// fewer file descriptors. Scanning a directory briefly uses two more.
+    ///
+    /// The minimum is 2, and the default is 64.
+    pub fn max_open_dirs(&mut self, max: usize) -> &mut Self {
+        self.max_open_dirs = max.max(2);
+        self
+    }
+
+    /// Returns `true` if `e` is the error returned when
+    /// [`RemoveOptions::one_file_system`] refused to cross into another file
+    /// system.
+    pub fn is_other_file_system(e: &Error) -> bool {
+        e.get_ref().is_some_and(|e| e.is::<OtherFileSystem>())
+    }
+
+    /// Returns `true` if `e` is the error returned when a directory being
+    /// removed was swapped for another one, for instance by renaming, while
+    /// the removal was in progress.
+    ///
+    /// Directories are checked by device and inode number whenever they are
+    /// re-opened or removed, so the other directory is left alone. Windows
+    /// removes directories through their handle, and never reports this.
+    pub fn is_directory_replaced(e: &Error) -> bool {
+        e.get_ref().is_some_and(|e| e.is::<DirectoryReplaced>())
+    }
+
+    /// As [`remove_dir_all`](crate::remove_dir_all), with these options.
+    pub fn remove_dir_all<P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(&self, path: P, generate: G) -> Result<()> {
+        let path = normpath::PathExt::normalize(path.as_ref())?;
+        super::remove_dir_all_path(path, &Context::new(self, &OsIo), generate)
+    }
+
+    /// As [`RemoveOptions::remove_dir_all`], performing the removal through
+    /// `io`, such as a [`FaultyIo`](crate::FaultyIo).
+    #[cfg(all(feature = "fault-injection", not(windows)))]
+    pub fn remove_dir_all_with<I: Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(
+        &self,
+        io: &I,
+        path: P,
+        generate: G,
+    ) -> Result<()> {
+        let path = normpath::PathExt::normalize(path.as_ref())?;
+        super::remove_dir_all_path(path, &Context::new(self, io), generate)
+    }
+
+    /// As [`RemoveOptions::remove_dir_contents`], performing the removal
+    /// through `io`, such as a [`FaultyIo`](crate::FaultyIo).
+    #[cfg(all(feature = "fault-injection", not(windows)))]
+    pub fn remove_dir_contents_with<I: Io, P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(
+        &self,
+        io: &I,
+        path: P,
+        generate: G,
+    ) -> Result<()> {
+        super::_remove_dir_contents_path(path, &Context::new(self, io), generate)
+    }
+
+    /// As [`remove_dir_all_deferred`](crate::remove_dir_all_deferred), with
+    /// these options.
+    ///
+    /// A cancellation token set with [`RemoveOptions::cancellation_token`]
+    /// is shared with the returned handle.
+    pub fn remove_dir_all_deferred<P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Send + Sync + 'static>(
+        &self,
+        path: P,
+        generate: G,
+    ) -> Result<DeferredRemoval> {
+        let trash = super::deferred::move_to_trash(path.as_ref())?;
+        self.deferred(trash, generate)
+    }
+
+    /// As [`resume_deferred`](crate::resume_deferred), with these options.
+    pub fn resume_deferred<P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Send + Sync + 'static>(
+        &self,
+        dir: P,
+        generate: G,
+    ) -> Result<DeferredRemoval> {
+        let trash = super::deferred::abandoned_trash(dir.as_ref())?;
+        self.deferred(trash, generate)
+    }
+
+    fn deferred<G: Fn(&OsStr) -> &Path + Send + Sync + 'static>(&self, trash: Trash, generate: G) -> Result<DeferredRemoval> {
+        let mut options = self.clone();
+        let cancel = options.cancel.get_or_insert_with(CancellationToken::new).clone();
+        DeferredRemoval::spawn(options, cancel, trash, generate)
+    }
+
+    /// As [`remove_dir_contents`](crate::remove_dir_contents), with these options.
+    pub fn remove_dir_contents<P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(&self, path: P, generate: G) -> Result<()> {
+        super::_remove_dir_contents_path(path, &Context::new(self, &OsIo), generate)
+    }
+
+    /// As [`ensure_empty_dir`](crate::ensure_empty_dir), with these options.
+    pub fn ensure_empty_dir<P: AsRef<Path>, G: Fn(&OsStr) -> &Path + Sync>(&self, path: P, generate: G) -> Result<()> {
+        super::_ensure_empty_dir_path(path, &Context::new(self, &OsIo), generate)
+    }
+
+    /// As [`RemoveDir::remove_dir_contents`](crate::RemoveDir::remove_dir_contents),
+    /// with these options.
+    pub fn remove_open_dir_contents<G: Fn(&OsStr) -> &Path + Sync>(
+        &self,
+        dir: &mut File,
+        debug_root: Option<&Path>,
+        generate: G,
+    ) -> Result<()> {
+        super::_remove_open_dir_contents(dir, debug_root, &Context::new(self, &OsIo), generate)
+    }
+
+    /// As [`plan_remove_dir_all`](crate::plan_remove_dir_all), with these
+    /// options.
+    ///
+    /// [`RemoveOptions::one_file_system`] is checked while planning, and
+    /// directories skipped by [`RemoveOptions::skip_other_file_systems`] are
+    /// [kept](crate::PlanEntry::is_kept). [`RemoveOptions::fix_permissions`]
+    /// is ignored: planning never changes the tree.
+    #[cfg(not(windows))]
+    pub fn plan_remove_dir_all<G: Fn(&OsStr) -> &Path + Sync>(&self, dir: &mut File, generate: G) -> Result<Plan> {
+        let mut options = self.clone();
+        options.fix_permissions = false;
+        super::plan::_plan(&Context::new(&options, &OsIo), dir, &generate)
+    }
+
+    /// As [`Plan::execute`], with these options.
+    ///
+    /// Progress is reported for every removed entry, and the removal can be
+    /// cancelled. [`RemoveOptions::fix_permissions`] applies to removing
+    /// entries. [`RemoveOptions::one_file_system`] has no effect here: it
+    /// applies while planning.
+    #[cfg(not(windows))]
+    pub fn execute<G: Fn(&OsStr) -> &Path + Sync>(&self, plan: &Plan, dir: &mut File, generate: G) -> Result<()> {
+        super::plan::_execute(&Context::new(self, &OsIo), plan, dir, &generate)
+    }
+
+    /// As [`RemoveOptions::plan_remove_dir_all`], reading the tree through
+    /// `io`, such as a [`FaultyIo`](crate::FaultyIo).
+    #[cfg(all(feature = "fault-injection", not(windows)))]
+    pub fn plan_remove_dir_all_with<I: Io, G: Fn(&OsStr) -> &Path + Sync>(
+        &self,
+        io: &I,
+        dir: &mut File,
+        generate: G,
+    ) -> Result<Plan> {
+        let mut options = self.clone();
+        options.fix_permissions = false;
+        super::plan::_plan(&Context::new(&options, io), dir, &generate)
+    }
+
+    /// As [`RemoveOptions::execute`], performing the removal through `io`,
+    /// such as a [`FaultyIo`](crate::FaultyIo).
+    #[cfg(all(feature = "fault-injection", not(windows)))]
+    pub fn execute_with<I: Io, G: Fn(&OsStr) -> &Path + Sync>(
+        &self,
+        io: &I,
+        plan: &Plan,
+        dir: &mut File,
+        generate: G,
+    ) -> Result<()> {
+        super::plan::_execute(&Context::new(self, io), plan, dir, &generate)
+    }
+}
+
+/// The state of one removal.
+pub(crate) struct Context<'o, I: Io> {
+    options: &'o RemoveOptions,
+    pub(crate) io: &'o I,
+    entries: AtomicU64,
+    bytes: AtomicU64,
+    root_fs: OnceLock<(u64, Option<u64>)>,
+}
+
+impl<'o, I: Io> Context<'o, I> {
+    pub(crate) fn new(options: &'o RemoveOptions, io: &'o I) -> Self {
+        Context {
+            options,
+            io,
+            entries: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            root_fs: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn max_open_dirs(&self) -> usize {
+        self.options.max_open_dirs
+    }
+
+    #[cfg(not(windows))]
+    pub(crate) fn fix_permissions(&self) -> bool {
+        self.options.fix_permissions
+    }
+
+    /// Records the directory being removed, for comparison with its children.
+    pub(crate) fn set_root(&self, d: &File) -> Result<()> {
+        if self.options.one_file_system {
+            let _ = self.root_fs.set(self.file_system(d)?);
+        }
+        Ok(())
+    }
+
+    /// Returns whether the child directory `d` should be skipped because it is
+    /// on another file system, or fails if that is not allowed.
+    pub(crate) fn skip_file_system(&self, d: &File, path: &PathComponents<'_>) -> Result<bool> {
+        let (root_dev, root_mount_id) = match self.root_fs.get() {
+            Some(root_fs) => *root_fs,
+            None => return Ok(false),
+        };
+        let (dev, mount_id) = self.file_system(d)?;
+        if dev == root_dev && (mount_id.is_none() || root_mount_id.is_none() || mount_id == root_mount_id) {
+            return Ok(false);
+        }
+        if self.options.skip_other_file_systems {
+            #[cfg(feature = "log")]
+            log::debug!("skipping {}: on a different file system", path);
+            Ok(true)
+        } else {
+            Err(Error::other(OtherFileSystem(path.to_string())))
+        }
+    }
+
+    /// The device and, if known, the mount ID of `d`.
+    fn file_system(&self, d: &File) -> Result<(u64, Option<u64>)> {
+        Ok((self.io.device(d)?, self.io.mount_id(d)?))
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match &self.options.cancel {
+            Some(token) if token.is_cancelled() => Err(Error::other(Cancelled)),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn removed(&self, path: &PathComponents<'_>, len: u64) {
+        let entries = self.entries.fetch_add(1, Ordering::Relaxed) + 1;
+        let bytes = self.bytes.fetch_add(len, Ordering::Relaxed) + len;
+        if let Some(progress) = &self.options.progress {
+            progress(&Progress {
+                entries,
+                bytes,
+                path,
+            });
+        }
+    }
+}
@@ -1,11 +1,20 @@
 use std::{fmt::Display, path::Path};
 
 /// Print a path that is broken into segments.
+///
+/// Removal never joins paths; the path of an entry is instead kept as a chain
+/// of its ancestors, which displays as the full path.
 // explicitly typed to avoid type recursion. 'a is the smallest lifetime present
 // : that of the child.
-pub(crate) enum PathComponents<'a> {
+#[derive(Debug)]
+pub enum PathComponents<'a> {
+    /// The path of the root of the removal.
     Path(&'a Path),
+    /// An entry inside its parent.
     Component(&'a PathComponents<'a>, &'a Path),
+    /// An entry below its ancestor, with the names of the directories in
+    /// between and its own name, outermost first.
+    Nested(&'a PathComponents<'a>, &'a [&'a Path]),
 }
 
 impl<'p> Display for PathComponents<'p> {
@@ -17,6 +26,14 @@ impl<'p> Display for PathComponents<'p> {
                 f.write_str("/")?;
                 c.display().fmt(f)
             }
+            PathComponents::Nested(p, names) => {
+                p.fmt(f)?;
+                for c in names.iter() {
+                    f.write_str("/")?;
+                    c.display().fmt(f)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,463 @@
+//! Planning deletions ahead of performing them.
+
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use super::io::{Io, Stat};
+use super::options::{Context, RemoveOptions};
+use super::path_components::PathComponents;
+use super::Walk;
+
+/// The kind of a directory entry in a [`Plan`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    /// A regular file, or anything else that is unlinked rather than scanned
+    /// (fifos, sockets, device nodes).
+    File,
+    /// A directory; its contents are removed before it is.
+    Dir,
+    /// A symlink. Symlinks are removed, never followed.
+    Symlink,
+}
+
+/// An entry that a [`Plan`] will remove.
+#[derive(Clone, Debug)]
+pub struct PlanEntry {
+    name: OsString,
+    stat: Stat,
+    children: Vec<PlanEntry>,
+    kept: bool,
+}
+
+impl PlanEntry {
+    /// The name of the entry within its parent directory.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// The kind of the entry when it was planned.
+    pub fn kind(&self) -> EntryKind {
+        self.stat.kind
+    }
+
+    /// The size in bytes of the entry when it was planned.
+    pub fn len(&self) -> u64 {
+        self.stat.len
+    }
+
+    /// Returns `true` if this entry had a size of zero bytes.
+    pub fn is_empty(&self) -> bool {
+        self.stat.len == 0
+    }
+
+    /// The entries inside this entry, if it is a directory.
+    pub fn children(&self) -> &[PlanEntry] {
+        &self.children
+    }
+
+    /// Returns `true` if this directory is left in place: it is on another
+    /// file system and was skipped because of
+    /// [`RemoveOptions::skip_other_file_systems`], or it contains such a
+    /// directory. A skipped directory has no children in the plan.
+    pub fn is_kept(&self) -> bool {
+        self.kept
+    }
+}
+
+/// The result of [`plan_remove_dir_all`](crate::plan_remove_dir_all): the
+/// entries of a directory tree that would be removed, and their totals.
+///
+/// Entries are recorded together with their device and inode numbers. When
+/// the plan is executed, an entry that was replaced, or that appeared, in the
+/// meantime is refused with an error instead of being removed.
+#[derive(Clone, Debug)]
+pub struct Plan {
+    root: Stat,
+    entries: Vec<PlanEntry>,
+    files: u64,
+    dirs: u64,
+    symlinks: u64,
+    bytes: u64,
+}
+
+impl Plan {
+    /// The entries of the planned directory.
+    pub fn entries(&self) -> &[PlanEntry] {
+        &self.entries
+    }
+
+    /// Returns `true` if every entry of the planned directory will be removed,
+    /// that is, none of them [is kept](PlanEntry::is_kept).
+    pub fn is_complete(&self) -> bool {
+        self.entries.iter().all(|e| !e.kept)
+    }
+
+    /// The number of non-directory, non-symlink entries in the plan.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of directories in the plan, not counting the planned
+    /// directory itself or [kept](PlanEntry::is_kept) directories.
+    pub fn dirs(&self) -> u64 {
+        self.dirs
+    }
+
+    /// The number of symlinks in the plan.
+    pub fn symlinks(&self) -> u64 {
+        self.symlinks
+    }
+
+    /// The total size in bytes of the files in the plan. Directories and
+    /// symlinks count as zero bytes, as in [`Progress::bytes`].
+    ///
+    /// [`Progress::bytes`]: crate::Progress::bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Calls `f` on every entry of the plan in the order they would be
+    /// removed: the contents of a directory before the directory itself. The
+    /// path of each entry is displayed relative to the planned directory.
+    /// [Kept](PlanEntry::is_kept) directories are left out.
+    pub fn for_each<F: FnMut(&dyn std::fmt::Display, &PlanEntry)>(&self, mut f: F) {
+        fn visit<F: FnMut(&dyn std::fmt::Display, &PlanEntry)>(
+            entries: &[PlanEntry],
+            parent: Option<&PathComponents<'_>>,
+            f: &mut F,
+        ) {
+            for entry in entries {
+                let name = Path::new(&entry.name);
+                let path = match parent {
+                    None => PathComponents::Path(name),
+                    Some(parent) => PathComponents::Component(parent, name),
+                };
+                visit(&entry.children, Some(&path), f);
+                if !entry.kept {
+                    f(&path, entry);
+                }
+            }
+        }
+        visit(&self.entries, None, &mut f)
+    }
+
+    /// Removes the planned entries from `dir`, which must be the directory the
+    /// plan was made for.
+    ///
+    /// Each entry is compared to the plan right before it is removed. If an
+    /// entry was replaced or changed kind, or a directory gained entries that
+    /// are not in the plan, an error of kind [`ErrorKind::Other`] is returned
+    /// and nothing further is removed. Entries that disappeared in the
+    /// meantime are skipped. Entries that were removed before the error was
+    /// detected stay removed. [Kept](PlanEntry::is_kept) directories are not
+    /// removed, and skipped directories are not entered.
+    ///
+    /// Use [`RemoveOptions::execute`] to report progress, cancel, or limit
+    /// the number of open directories.
+    pub fn execute<G: Fn(&OsStr) -> &Path + Sync>(&self, dir: &mut File, generate: G) -> Result<()> {
+        RemoveOptions::new().execute(self, dir, generate)
+    }
+}
+
+/// The state of a directory being planned.
+struct Planning {
+    /// The entry of the directory itself, collecting its children.
+    entry: PlanEntry,
+    /// Subdirectories still to be planned, with their metadata from the scan;
+    /// `None` until the directory has been scanned.
+    subdirs: Option<Vec<(OsString, Stat)>>,
+}
+
+// Directories are planned depth first on the same work stack as removal, so
+// that neither the call stack nor the number of open directories grow with the
+// depth of the tree.
+pub(crate) fn _plan<I: Io, G: Fn(&OsStr) -> &Path + Sync>(ctx: &Context<'_, I>, d: &mut File, generate: &G) -> Result<Plan> {
+    let io = ctx.io;
+    let root = io.stat(d)?;
+    if root.kind != EntryKind::Dir {
+        return Err(Error::new(ErrorKind::InvalidInput, "not a directory"));
+    }
+    let mut plan = Plan {
+        root,
+        entries: Vec::new(),
+        files: 0,
+        dirs: 0,
+        symlinks: 0,
+        bytes: 0,
+    };
+    let debug_root = PathComponents::Path(generate(".".as_ref()));
+    let planning = Planning {
+        entry: PlanEntry {
+            name: OsString::new(),
+            stat: root,
+            children: Vec::new(),
+            kept: false,
+        },
+        subdirs: None,
+    };
+    ctx.set_root(d)?;
+    let mut walk = Walk::new(ctx, io.duplicate_fd(d)?, planning)?;
+    loop {
+        let top = walk.stack.len() - 1;
+        walk.reopen(top, &debug_root, generate)?;
+        let frame = &mut walk.stack[top];
+        if frame.data.subdirs.is_none() {
+            let dir = frame.dir.as_mut().expect("reopened before use");
+            let subdirs = scan(io, dir, &mut plan, &mut frame.data.entry.children, generate)?;
+            frame.data.subdirs = Some(subdirs);
+        }
+
+        if let Some((name, stat)) = frame.data.subdirs.as_mut().and_then(|s| s.pop()) {
+            ctx.check_cancelled()?;
+            let names: Vec<&Path> = walk.names.iter().map(|n| generate(n)).collect();
+            let debug_path = PathComponents::Nested(&debug_root, &names);
+            let child_debug_path = PathComponents::Component(&debug_path, generate(&name));
+            let child = open_dir_at(io, walk.dir(top), generate(&name), &stat, &child_debug_path)?;
+            let mut entry = PlanEntry {
+                name: name.clone(),
+                stat,
+                children: Vec::new(),
+                kept: false,
+            };
+            if ctx.skip_file_system(&child, &child_debug_path)? {
+                entry.kept = true;
+                plan.dirs -= 1;
+                mark_kept(&mut walk.stack[top].data.entry, &mut plan);
+                walk.stack[top].data.entry.children.push(entry);
+                continue;
+            }
+            drop(names);
+            let planning = Planning {
+                entry,
+                subdirs: None,
+            };
+            walk.push(name, child, planning)?;
+            continue;
+        }
+
+        if top == 0 {
+            break;
+        }
+        let (_, frame) = walk.pop();
+        let parent = &mut walk.stack[top - 1].data.entry;
+        if frame.data.entry.kept {
+            mark_kept(parent, &mut plan);
+        }
+        parent.children.push(frame.data.entry);
+    }
+    let root = walk.stack.pop().expect("the root is never popped");
+    plan.entries = root.data.entry.children;
+    Ok(plan)
+}
+
+/// Marks a directory as kept, as it contains a kept directory, and takes it off
+/// the totals of `plan`.
+fn mark_kept(dir: &mut PlanEntry, plan: &mut Plan) {
+    // The planned directory itself is never removed, nor counted.
+    if !dir.kept && !dir.name.is_empty() {
+        plan.dirs -= 1;
+    }
+    dir.kept = true;
+}
+
+/// Records the entries of `d` in `children` and adds them to the totals of
+/// `plan`. Subdirectories are counted, but returned to be planned later
+/// instead of recorded.
+fn scan<I: Io, G: Fn(&OsStr) -> &Path + Sync>(
+    io: &I,
+    d: &mut File,
+    plan: &mut Plan,
+    children: &mut Vec<PlanEntry>,
+    generate: &G,
+) -> Result<Vec<(OsString, Stat)>> {
+    let mut subdirs = Vec::new();
+    for dir_entry in io.read_dir(&mut io.duplicate_fd(d)?)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.name();
+        if name == OsStr::new(".") || name == OsStr::new("..") {
+            continue;
+        }
+        let stat = io.stat_at(d, generate(name))?;
+        match stat.kind {
+            EntryKind::Dir => {
+                plan.dirs += 1;
+                subdirs.push((name.to_owned(), stat));
+                continue;
+            }
+            EntryKind::Symlink => plan.symlinks += 1,
+            EntryKind::File => {
+                plan.files += 1;
+                plan.bytes += stat.len;
+            }
+        }
+        children.push(PlanEntry {
+            name: name.to_owned(),
+            stat,
+            children: Vec::new(),
+            kept: false,
+        });
+    }
+    Ok(subdirs)
+}
+
+/// The state of a directory whose planned entries are being removed.
+struct Execution<'p> {
+    /// The planned entries of the directory.
+    entries: &'p [PlanEntry],
+    /// The metadata of the directory itself when it was planned.
+    stat: Stat,
+    /// Whether the directory is kept after removing its entries.
+    kept: bool,
+    /// The index of the next entry to remove; `None` until the directory has
+    /// been checked for entries that are not in the plan.
+    next: Option<usize>,
+}
+
+pub(crate) fn _execute<I: Io, G: Fn(&OsStr) -> &Path + Sync>(ctx: &Context<'_, I>, plan: &Plan, dir: &mut File, generate: &G) -> Result<()> {
+    let io = ctx.io;
+    let debug_root = PathComponents::Path(generate(".".as_ref()));
+    if !same_entry(&plan.root, &io.stat(dir)?) {
+        return Err(changed(&debug_root));
+    }
+    let execution = Execution {
+        entries: &plan.entries,
+        stat: plan.root,
+        kept: true,
+        next: None,
+    };
+    let mut walk = Walk::new(ctx, io.duplicate_fd(dir)?, execution)?;
+    loop {
+        let top = walk.stack.len() - 1;
+        walk.reopen(top, &debug_root, generate)?;
+        let names: Vec<&Path> = walk.names.iter().map(|n| generate(n)).collect();
+        let debug_path = PathComponents::Nested(&debug_root, &names);
+
+        let frame = &mut walk.stack[top];
+        let entries = frame.data.entries;
+        let next = match frame.data.next {
+            Some(next) => next,
+            None => {
+                // Refuse to remove a directory that gained entries, before
+                // touching any of its contents.
+                let dir = frame.dir.as_mut().expect("reopened before use");
+                check_planned(io, dir, entries, &debug_path, generate)?;
+                0
+            }
+        };
+        frame.data.next = Some(next + 1);
+
+        if let Some(entry) = entries.get(next) {
+            ctx.check_cancelled()?;
+            // A directory on another file system, skipped while planning.
+            if entry.kept && entry.children.is_empty() {
+                continue;
+            }
+            let name = generate(&entry.name);
+            let entry_debug_path = PathComponents::Component(&debug_path, name);
+            let d = walk.dir(top);
+            let stat = match io.stat_at(d, name) {
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                otherwise => otherwise?,
+            };
+            if !same_entry(&entry.stat, &stat) {
+                return Err(changed(&entry_debug_path));
+            }
+            if stat.kind == EntryKind::Dir {
+                let child = open_dir_at(io, d, name, &entry.stat, &entry_debug_path)?;
+                drop(names);
+                let execution = Execution {
+                    entries: &entry.children,
+                    stat: entry.stat,
+                    kept: entry.kept,
+                    next: None,
+                };
+                walk.push(entry.name.clone(), child, execution)?;
+            } else {
+                #[cfg(feature = "log")]
+                log::trace!("unlink: {}", &entry_debug_path);
+                super::fixing_permissions(ctx, d, || io.unlink_at(d, name))?;
+                let len = if stat.kind == EntryKind::File { stat.len } else { 0 };
+                ctx.removed(&entry_debug_path, len);
+            }
+            continue;
+        }
+
+        if top == 0 {
+            return Ok(());
+        }
+        drop(names);
+        let (name, frame) = walk.pop();
+        let Execution { stat: expected, kept, .. } = frame.data;
+        drop(frame);
+        if kept {
+            continue;
+        }
+        walk.reopen(top - 1, &debug_root, generate)?;
+        let names: Vec<&Path> = walk.names.iter().map(|n| generate(n)).collect();
+        let parent_debug_path = PathComponents::Nested(&debug_root, &names);
+        let debug_path = PathComponents::Component(&parent_debug_path, generate(&name));
+        let d = walk.dir(top - 1);
+        // Don't remove an empty directory that was swapped in meanwhile.
+        let stat = match io.stat_at(d, generate(&name)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            otherwise => otherwise?,
+        };
+        if !same_entry(&expected, &stat) {
+            return Err(changed(&debug_path));
+        }
+        #[cfg(feature = "log")]
+        log::trace!("rmdir: {}", debug_path);
+        super::fixing_permissions(ctx, d, || io.rmdir_at(d, generate(&name)))?;
+        ctx.removed(&debug_path, 0);
+    }
+}
+
+/// Fails if `d` contains an entry that is not one of `entries`.
+fn check_planned<I: Io, G: Fn(&OsStr) -> &Path + Sync>(
+    io: &I,
+    d: &mut File,
+    entries: &[PlanEntry],
+    debug_path: &PathComponents<'_>,
+    generate: &G,
+) -> Result<()> {
+    let planned: HashSet<&OsStr> = entries.iter().map(|e| e.name.as_os_str()).collect();
+    for dir_entry in io.read_dir(&mut io.duplicate_fd(d)?)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.name();
+        if name == OsStr::new(".") || name == OsStr::new("..") {
+            continue;
+        }
+        if !planned.contains(name) {
+            return Err(changed(&PathComponents::Component(debug_path, generate(name))));
+        }
+    }
+    Ok(())
+}
+
+/// Opens the directory `p` in `d`, checking it is still the entry `expected`.
+fn open_dir_at<I: Io>(io: &I, d: &File, p: &Path, expected: &Stat, debug_path: &PathComponents<'_>) -> Result<File> {
+    let child = match io.open_dir_at(d, p) {
+        // Replaced by a symlink since the stat
+        Err(e) if io.is_eloop(&e) => return Err(changed(debug_path)),
+        otherwise => otherwise?,
+    };
+    if !same_entry(expected, &io.stat(&child)?) {
+        return Err(changed(debug_path));
+    }
+    Ok(child)
+}
+
+/// Only identity and kind are compared: directory sizes change as their
+/// contents are removed.
+fn same_entry(a: &Stat, b: &Stat) -> bool {
+    a.kind == b.kind && a.dev == b.dev && a.ino == b.ino
+}
+
+fn changed(path: &PathComponents<'_>) -> Error {
+    Error::other(format!("{path} changed since the removal was planned"))
+}
@@ -1,5 +1,8 @@
+use std::ffi::CString;
 use std::fs::{File, OpenOptions};
 use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::prelude::FromRawFd;
 use std::path::Path;
@@ -8,14 +11,16 @@ use std::{fs, os::unix::prelude::AsRawFd};
 use cvt::cvt;
 use libc::{self, fcntl, F_DUPFD_CLOEXEC};
 
-use super::io::Io;
+use super::io::{Io, Stat};
+use crate::EntryKind;
 
 pub(crate) struct UnixIo;
 
 impl Io for UnixIo {
-    type UniqueIdentifier = ();
+    /// Device and inode number.
+    type UniqueIdentifier = (u64, u64);
 
-    fn duplicate_fd(f: &mut fs::File) -> io::Result<fs::File> {
+    fn duplicate_fd(&self, f: &mut fs::File) -> io::Result<fs::File> {
         let source_fd = f.as_raw_fd();
         // F_DUPFD_CLOEXEC seems to be quite portable, but we should be prepared
         // to add in more codepaths here.
@@ -23,18 +28,52 @@ impl Io for UnixIo {
         Ok(unsafe { File::from_raw_fd(fd) })
     }
 
-    fn open_dir(p: &Path) -> io::Result<fs::File> {
+    fn open_dir(&self, p: &Path) -> io::Result<fs::File> {
         let mut options = OpenOptions::new();
         options.read(true);
         options.custom_flags(libc::O_NOFOLLOW);
         options.open(p)
     }
 
-    fn unique_identifier(_d: &fs::File) -> io::Result<Self::UniqueIdentifier> {
-        todo!()
+    fn device(&self, f: &fs::File) -> io::Result<u64> {
+        Ok(self.stat(f)?.dev)
     }
 
-    fn is_eloop(e: &io::Error) -> bool {
+    /// Requires statx with `STATX_MNT_ID` (Linux 5.8).
+    #[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+    fn mount_id(&self, f: &fs::File) -> io::Result<Option<u64>> {
+        let mut stx = MaybeUninit::<libc::statx>::zeroed();
+        let result = cvt(unsafe {
+            libc::statx(
+                f.as_raw_fd(),
+                b"\0".as_ptr().cast(),
+                libc::AT_EMPTY_PATH,
+                libc::STATX_MNT_ID,
+                stx.as_mut_ptr(),
+            )
+        });
+        match result {
+            // Kernels before 4.11, or seccomp filters that don't know statx.
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENOSYS) | Some(libc::EPERM)) => Ok(None),
+            Err(e) => Err(e),
+            Ok(_) => {
+                let stx = unsafe { stx.assume_init() };
+                Ok(Some(stx.stx_mnt_id).filter(|_| stx.stx_mask & libc::STATX_MNT_ID != 0))
+            }
+        }
+    }
+
+    fn unique_identifier(&self, d: &fs::File) -> io::Result<Self::UniqueIdentifier> {
+        let stat = self.stat(d)?;
+        Ok((stat.dev, stat.ino))
+    }
+
+    fn unique_identifier_at(&self, d: &fs::File, p: &Path) -> io::Result<Self::UniqueIdentifier> {
+        let stat = self.stat_at(d, p)?;
+        Ok((stat.dev, stat.ino))
+    }
+
+    fn is_eloop(&self, e: &io::Error) -> bool {
         // When the `NO_FOLLOW` flag is set, POSIX specifies that ELOOP be turned
         // if the trailing component is a symlink.
         // However, not all platforms follow POSIX on this.
@@ -51,4 +90,76 @@ impl Io for UnixIo {
             }
         }
     }
+
+    fn stat(&self, f: &fs::File) -> io::Result<Stat> {
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        cvt(unsafe { libc::fstat(f.as_raw_fd(), st.as_mut_ptr()) })?;
+        Ok(to_stat(unsafe { &st.assume_init() }))
+    }
+
+    fn stat_at(&self, d: &fs::File, p: &Path) -> io::Result<Stat> {
+        let p = CString::new(p.as_os_str().as_bytes())?;
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        cvt(unsafe {
+            libc::fstatat(
+                d.as_raw_fd(),
+                p.as_ptr(),
+                st.as_mut_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+        Ok(to_stat(unsafe { &st.assume_init() }))
+    }
+
+    fn add_permissions(&self, d: &fs::File, mode: libc::mode_t) -> io::Result<()> {
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        cvt(unsafe { libc::fstat(d.as_raw_fd(), st.as_mut_ptr()) })?;
+        let st = unsafe { st.assume_init() };
+        cvt(unsafe { libc::fchmod(d.as_raw_fd(), (st.st_mode & 0o7777) | mode) })?;
+        Ok(())
+    }
+
+    fn add_permissions_at(&self, d: &fs::File, p: &Path, mode: libc::mode_t) -> io::Result<()> {
+        let p = CString::new(p.as_os_str().as_bytes())?;
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        cvt(unsafe {
+            libc::fstatat(
+                d.as_raw_fd(),
+                p.as_ptr(),
+                st.as_mut_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+        let st = unsafe { st.assume_init() };
+        if st.st_mode & libc::S_IFMT != libc::S_IFDIR {
+            return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+        }
+        // Refused rather than followed if the entry became a symlink since the
+        // fstatat: Linux does not support changing the mode of symlinks.
+        cvt(unsafe {
+            libc::fchmodat(
+                d.as_raw_fd(),
+                p.as_ptr(),
+                (st.st_mode & 0o7777) | mode,
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+        Ok(())
+    }
+}
+
+// The widths of these fields vary between platforms.
+#[allow(clippy::unnecessary_cast)]
+fn to_stat(st: &libc::stat) -> Stat {
+    let kind = match st.st_mode & libc::S_IFMT {
+        libc::S_IFDIR => EntryKind::Dir,
+        libc::S_IFLNK => EntryKind::Symlink,
+        _ => EntryKind::File,
+    };
+    Stat {
+        kind,
+        len: st.st_size as u64,
+        dev: st.st_dev as u64,
+        ino: st.st_ino as u64,
+    }
 }
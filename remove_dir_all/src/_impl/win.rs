@@ -11,7 +11,10 @@ use std::{
 
 use windows_sys::Win32::{
     Foundation::{DuplicateHandle, DUPLICATE_SAME_ACCESS, HANDLE},
-    Storage::FileSystem::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT},
+    Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS,
+        FILE_FLAG_OPEN_REPARSE_POINT,
+    },
     System::Threading::GetCurrentProcess,
 };
 
@@ -20,7 +23,7 @@ use super::io::Io;
 pub(crate) struct WindowsIo;
 
 impl Io for WindowsIo {
-    fn duplicate_fd(f: &mut File) -> io::Result<File> {
+    fn duplicate_fd(&self, f: &mut File) -> io::Result<File> {
         let mut new_handle: MaybeUninit<*mut c_void> = MaybeUninit::uninit();
 
         let result = unsafe {
@@ -42,7 +45,7 @@ impl Io for WindowsIo {
         Ok(unsafe { File::from_raw_handle(new_handle) })
     }
 
-    fn open_dir(p: &Path) -> Result<File> {
+    fn open_dir(&self, p: &Path) -> Result<File> {
         let mut options = OpenOptions::new();
         options.read(true);
         options.custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT);
@@ -55,4 +58,15 @@ impl Io for WindowsIo {
         }
         Ok(maybe_dir)
     }
+
+    fn device(&self, f: &File) -> Result<u64> {
+        let mut info: MaybeUninit<BY_HANDLE_FILE_INFORMATION> = MaybeUninit::uninit();
+        let result =
+            unsafe { GetFileInformationByHandle(f.as_raw_handle() as HANDLE, info.as_mut_ptr()) };
+        if result == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let info = unsafe { info.assume_init() };
+        Ok(info.dwVolumeSerialNumber as u64)
+    }
 }
@@ -1,6 +1,11 @@
-use std::{io::Result, path::PathBuf};
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
 
 use clap::Parser;
+use remove_dir_all::RemoveOptions;
 
 /// Simple CLI to use remove-dir-alls recursive deletion logic from the command
 /// line.
@@ -10,14 +15,102 @@ struct Cli {
     /// Paths to delete
     #[arg(value_name = "FILE")]
     names: Vec<PathBuf>,
+
+    /// Delete the contents of each directory but keep the directory itself
+    #[arg(long, conflicts_with = "ensure_empty")]
+    contents_only: bool,
+
+    /// Like --contents-only, but create directories that do not exist
+    #[arg(long)]
+    ensure_empty: bool,
+
+    /// Print what would be deleted, children before their parents, without
+    /// deleting anything
+    #[arg(long, short = 'n')]
+    dry_run: bool,
+
+    /// Stop with an error on reaching a directory on a different file system
+    /// than the directory being deleted
+    #[arg(long, short = 'x')]
+    one_file_system: bool,
+
+    /// Add owner write and search permission to directories that deny
+    /// deleting their contents
+    #[arg(long)]
+    fix_permissions: bool,
+
+    /// Number of threads to delete with. Defaults to the number of CPUs
+    #[arg(long, short = 'j', value_name = "N", conflicts_with = "serial")]
+    threads: Option<usize>,
+
+    /// Delete with a single thread; equivalent to --threads 1
+    #[arg(long)]
+    serial: bool,
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let cli = Cli::parse();
 
-    for p in cli.names {
-        remove_dir_all::remove_dir_all(p)?;
+    let threads = if cli.serial { Some(1) } else { cli.threads };
+    if let Some(threads) = threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(Error::other)?;
+    }
+
+    let mut options = RemoveOptions::new();
+    options
+        .one_file_system(cli.one_file_system)
+        .fix_permissions(cli.fix_permissions);
+
+    for p in &cli.names {
+        if cli.ensure_empty && fs::symlink_metadata(p).is_err() {
+            if cli.dry_run {
+                println!("would create {}", p.display());
+            } else {
+                options.ensure_empty_dir(p, |s| Path::new(s))?;
+            }
+            continue;
+        }
+        if cli.dry_run {
+            dry_run(p, &cli, &options)?;
+            continue;
+        }
+        if cli.ensure_empty {
+            options.ensure_empty_dir(p, |s| Path::new(s))?;
+        } else if cli.contents_only {
+            options.remove_dir_contents(p, |s| Path::new(s))?;
+        } else {
+            options.remove_dir_all(p, |s| Path::new(s))?;
+        }
+    }
+    Ok(())
+}
+
+/// Print what removing `dir` would remove, in deletion order, from the plan
+/// the removal itself would follow.
+#[cfg(not(windows))]
+fn dry_run(dir: &Path, cli: &Cli, options: &RemoveOptions) -> Result<()> {
+    if !fs::symlink_metadata(dir)?.is_dir() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not a directory", dir.display()),
+        ));
+    }
+    let plan = options.plan_remove_dir_all(&mut fs::File::open(dir)?, |s| Path::new(s))?;
+    plan.for_each(|path, _| println!("would remove {}", dir.join(path.to_string()).display()));
+    if !(cli.contents_only || cli.ensure_empty) && plan.is_complete() {
+        println!("would remove {}", dir.display());
     }
     Ok(())
 }
+
+#[cfg(windows)]
+fn dry_run(_dir: &Path, _cli: &Cli, _options: &RemoveOptions) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "--dry-run is not supported on Windows",
+    ))
+}
@@ -14,7 +14,9 @@
 //! delete the files, and that they don't have permission to change permissions
 //! to be able to delete the files: no ACL or chmod changes are made during
 //! deletion. This is because hardlinks can cause such changes to show up and
-//! affect the filesystem outside of the directory tree being deleted.
+//! affect the filesystem outside of the directory tree being deleted. The
+//! opt-in [`RemoveOptions::fix_permissions`] is the exception: it only changes
+//! the permissions of directories, which can't be hardlinked.
 //!   
 //! The extension trait [`RemoveDir`] can be used to invoke `remove_dir_all` on
 //! an open [`File`](std::fs::File), where it will error if the file is not a directory,
@@ -32,6 +34,9 @@
 //!
 //! - parallel: When enabled, deletion of directories is parallised. (#parallel)[more details]
 //! - log: Include some log messages about the deletion taking place.
+//! - fault-injection: Provide [`FaultyIo`](crate::FaultyIo), a backend that
+//!   makes chosen operations fail, for testing code that removes trees. Unix
+//!   only.
 //!
 //! About the implementation. The implementation prioritises security, then
 //! robustness (e.g. low resource situations), and then finally performance.
@@ -52,11 +57,11 @@
 //! being deleted can prevent the directory being deleted for an arbitrary
 //! period by extending the directory iterator indefinitely.
 //!
-//! Directory traversal only ever happens downwards. In future, to accommodate
-//! very large directory trees (greater than file descriptor limits deep) the
-//! same path may be traversed multiple times, and the quadratic nature of that
-//! will be mitigated by a cache of open directories. See [#future-plans](Future
-//! Plans)
+//! Directory traversal only ever happens downwards. To accommodate very large
+//! directory trees (greater than file descriptor limits deep) only a bounded
+//! number of directories are held open (see [`RemoveOptions::max_open_dirs`]);
+//! the others are re-opened relative to their parent directory when needed,
+//! so the same path may be traversed multiple times.
 //!
 //! ## Robustness
 //!
@@ -74,11 +79,12 @@
 //!
 //! ## Serial deletion
 //!
-//! Serial deletion occurs recursively - open, read, delete
-//! contents-except-for-directories, repeat.
+//! Serial deletion works depth first from an explicit work stack rather than
+//! by recursion - open, read, delete contents-except-for-directories, then
+//! repeat for each directory.
 //!
 //! Parallel deletion builds on serial deletion by utilising a thread pool for
-//! IO which can block:
+//! IO which can block, within each directory:
 //! - directory scanning
 //! - calls to unlink and fstat
 //! - file handle closing (yes, that can block)
@@ -104,13 +110,9 @@
 //! ## Future Plans
 //!  Open directory handles are kept in
 //! a lg-spaced cache after the first 10 levels:
-//! level10/skipped1/level12/skipped2/skipped3/skipped4/level16. If EMFILE is
-//! encountered, no more handles are cached, and directories are opened by
-//! re-traversing from the closest previously opened handle. Deletion should
-//! succeed even only 4 file descriptors are available: one to hold the root,
-//! two to iterate individual directories, and one to open-and-delete individual
-//! files, though that will be quadratic in the depth of the tree, successfully
-//! deleting leaves only on each iteration.
+//! level10/skipped1/level12/skipped2/skipped3/skipped4/level16, rather than
+//! only the ones nearest the current directory. If EMFILE is encountered, no
+//! more handles are cached.
 //!
 //! IO Prioritisation:
 //! 1) directory scanning when few paths are queued for deletion (to avoid
@@ -133,8 +135,6 @@
 
 use std::{io::Result, path::Path};
 
-use normpath::PathExt;
-
 #[cfg(doctest)]
 #[macro_use]
 extern crate doc_comment;
@@ -144,12 +144,21 @@ doctest!("../README.md");
 
 mod _impl;
 
+pub use _impl::deferred::DeferredRemoval;
+pub use _impl::options::{CancellationToken, Progress, RemoveOptions};
+pub use _impl::path_components::PathComponents;
+#[cfg(all(feature = "fault-injection", not(windows)))]
+pub use _impl::fault::{Fault, FaultOp, FaultyIo};
+
+#[cfg(not(windows))]
+pub use _impl::plan::{EntryKind, Plan, PlanEntry};
+
 /// Extension trait adding `remove_dir_all` support to [`std::fs::File`].
 pub trait RemoveDir {
     /// Remove the contents of the dir.
     ///
     /// `debug_root`: identifies the directory contents being removed
-    fn remove_dir_contents<G: Fn(&std::ffi::OsStr) -> &Path>(&mut self, debug_root: Option<&Path>, generate: G) -> Result<()>;
+    fn remove_dir_contents<G: Fn(&std::ffi::OsStr) -> &Path + Sync>(&mut self, debug_root: Option<&Path>, generate: G) -> Result<()>;
 }
 
 /// Makes `path` an empty directory: if it does not exist, it is created it as
@@ -162,8 +171,8 @@ pub trait RemoveDir {
 /// This is subject to file system races: a privileged process could be attacked
 /// by replacing parent directories of the supplied path with a link (e.g. to
 /// /etc). Consider using [`RemoveDir::remove_dir_contents`] instead.
-pub fn ensure_empty_dir<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path>(path: P, generate: G) -> Result<()> {
-    _impl::_ensure_empty_dir_path::<_impl::OsIo, _, _>(path, generate)
+pub fn ensure_empty_dir<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path + Sync>(path: P, generate: G) -> Result<()> {
+    RemoveOptions::new().ensure_empty_dir(path, generate)
 }
 
 /// Deletes the contents of `path`, but not the directory itself. It is an error
@@ -172,8 +181,8 @@ pub fn ensure_empty_dir<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path>(path:
 /// This is subject to file system races: a privileged process could be attacked
 /// by replacing parent directories of the supplied path with a link (e.g. to
 /// /etc). Consider using [`RemoveDir::remove_dir_contents`] instead.
-pub fn remove_dir_contents<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path>(path: P, generate: G) -> Result<()> {
-    _impl::_remove_dir_contents_path::<_impl::OsIo, P, _>(path, generate)
+pub fn remove_dir_contents<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path + Sync>(path: P, generate: G) -> Result<()> {
+    RemoveOptions::new().remove_dir_contents(path, generate)
 }
 
 /// Reliably removes a directory and all of its children.
@@ -194,9 +203,74 @@ pub fn remove_dir_contents<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path>(pat
 /// determining what to delete: a privileged process could be attacked by
 /// replacing parent directories of the supplied path with a link (e.g. to
 /// /etc). Consider using [`RemoveDir::remove_dir_contents`] instead.
-pub fn remove_dir_all<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path>(path: P, generate: G) -> Result<()> {
-    let path = path.as_ref().normalize()?;
-    _impl::remove_dir_all_path::<_impl::OsIo, _, _>(path, generate)
+pub fn remove_dir_all<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path + Sync>(path: P, generate: G) -> Result<()> {
+    RemoveOptions::new().remove_dir_all(path, generate)
+}
+
+/// Moves the directory `path` out of the way, then removes it on a background
+/// thread.
+///
+/// The directory is renamed to a trash directory next to it, named
+/// `.remove_dir_all-trash.<pid>.<unique>`, so `path` is gone as soon as this
+/// returns. The returned [`DeferredRemoval`] can be used to wait for or cancel
+/// the removal of the trash directory. Trash left behind by a process that
+/// exited or crashed first can be removed with [`resume_deferred`].
+///
+/// ```rust,no_run
+/// use std::path::Path;
+///
+/// let removal = remove_dir_all::remove_dir_all_deferred("./target/", |s| Path::new(s))?;
+/// // ... carry on with other work ...
+/// removal.wait()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// It is an error if `path` is not a directory, including a symlink to a
+/// directory. As with [`remove_dir_all`], this is subject to races on the path
+/// to the directory.
+pub fn remove_dir_all_deferred<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path + Send + Sync + 'static>(
+    path: P,
+    generate: G,
+) -> Result<DeferredRemoval> {
+    RemoveOptions::new().remove_dir_all_deferred(path, generate)
+}
+
+/// Removes, on a background thread, the trash directories in `dir` left behind
+/// by [`remove_dir_all_deferred`] in processes that no longer run, or that
+/// were cancelled in this process.
+///
+/// Trash directories are claimed by renaming, so concurrent callers never
+/// remove the same one.
+pub fn resume_deferred<P: AsRef<Path>, G: Fn(&std::ffi::OsStr) -> &Path + Send + Sync + 'static>(
+    dir: P,
+    generate: G,
+) -> Result<DeferredRemoval> {
+    RemoveOptions::new().resume_deferred(dir, generate)
+}
+
+/// Plans the removal of the contents of the directory `dir`, without removing
+/// anything.
+///
+/// The tree is traversed with the same descriptor-relative operations as
+/// [`RemoveDir::remove_dir_contents`], so symlinks are recorded but never
+/// followed. The returned [`Plan`] describes every entry that would be
+/// removed, with counts and total bytes, and can be executed later with
+/// [`Plan::execute`], which refuses entries that changed in between.
+///
+/// ```rust,no_run
+/// use std::{fs::File, path::Path};
+///
+/// let mut dir = File::open("./temp/")?;
+/// let plan = remove_dir_all::plan_remove_dir_all(&mut dir, |s| Path::new(s))?;
+/// println!("{} files, {} bytes", plan.files(), plan.total_bytes());
+/// plan.execute(&mut dir, |s| Path::new(s))?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// This is not available on Windows.
+#[cfg(not(windows))]
+pub fn plan_remove_dir_all<G: Fn(&std::ffi::OsStr) -> &Path + Sync>(dir: &mut std::fs::File, generate: G) -> Result<Plan> {
+    RemoveOptions::new().plan_remove_dir_all(dir, generate)
 }
 
 #[allow(deprecated)]
@@ -249,10 +323,7 @@ mod tests {
                 println!("{e} {:?}, {:?}, {:?}", e.raw_os_error(), e.kind(), n);
                 Err(e)
             }
-            Ok(_) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                "unexpected success".to_string(),
-            )),
+            Ok(_) => Err(io::Error::other("unexpected success")),
         }
     }
 
@@ -310,4 +381,85 @@ mod tests {
 
         Ok(())
     }
+
+    /// Pretends that the directory at its path is a mount point.
+    #[cfg(not(windows))]
+    struct MountIo(PathBuf);
+
+    #[cfg(not(windows))]
+    impl crate::_impl::io::Io for MountIo {
+        type UniqueIdentifier = (u64, u64);
+
+        fn duplicate_fd(&self, f: &mut File) -> io::Result<File> {
+            crate::_impl::OsIo.duplicate_fd(f)
+        }
+
+        fn open_dir(&self, p: &Path) -> io::Result<File> {
+            crate::_impl::OsIo.open_dir(p)
+        }
+
+        fn device(&self, f: &File) -> io::Result<u64> {
+            use std::os::unix::fs::MetadataExt;
+
+            let dev = crate::_impl::OsIo.device(f)?;
+            match fs::symlink_metadata(&self.0) {
+                Ok(mnt) if mnt.ino() == f.metadata()?.ino() => Ok(dev + 1),
+                _ => Ok(dev),
+            }
+        }
+
+        fn unique_identifier(&self, d: &File) -> io::Result<(u64, u64)> {
+            crate::_impl::OsIo.unique_identifier(d)
+        }
+
+        fn unique_identifier_at(&self, d: &File, p: &Path) -> io::Result<(u64, u64)> {
+            crate::_impl::OsIo.unique_identifier_at(d, p)
+        }
+
+        fn is_eloop(&self, e: &io::Error) -> bool {
+            crate::_impl::OsIo.is_eloop(e)
+        }
+
+        fn stat(&self, f: &File) -> io::Result<crate::_impl::io::Stat> {
+            crate::_impl::OsIo.stat(f)
+        }
+
+        fn stat_at(&self, d: &File, p: &Path) -> io::Result<crate::_impl::io::Stat> {
+            crate::_impl::OsIo.stat_at(d, p)
+        }
+
+        fn add_permissions(&self, d: &File, mode: libc::mode_t) -> io::Result<()> {
+            crate::_impl::OsIo.add_permissions(d, mode)
+        }
+
+        fn add_permissions_at(&self, d: &File, p: &Path, mode: libc::mode_t) -> io::Result<()> {
+            crate::_impl::OsIo.add_permissions_at(d, p, mode)
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn one_file_system() -> Result<()> {
+        use crate::_impl::options::Context;
+        use crate::RemoveOptions;
+
+        let p = prep()?;
+        let mnt = p.ours.join("another_dir/mnt");
+        fs::create_dir(&mnt)?;
+        File::create(mnt.join("precious"))?;
+        let io = MountIo(mnt.clone());
+
+        let mut options = RemoveOptions::new();
+        options.one_file_system(true);
+        let err = crate::_impl::_remove_dir_contents_path(&p.ours, &Context::new(&options, &io), |s| Path::new(s))
+            .unwrap_err();
+        assert!(RemoveOptions::is_other_file_system(&err));
+        File::open(mnt.join("precious"))?;
+
+        options.skip_other_file_systems(true);
+        crate::_impl::_remove_dir_contents_path(&p.ours, &Context::new(&options, &io), |s| Path::new(s))?;
+        File::open(mnt.join("precious"))?;
+        expect_failure(&[ENOENT], File::open(&p.file))?;
+        Ok(())
+    }
 }
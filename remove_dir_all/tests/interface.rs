@@ -230,3 +230,542 @@ fn removes_symlinks() {
 }
 
 // TODO: Should probably test readonly hard links...
+
+// plan_remove_dir_all
+
+#[cfg(not(windows))]
+fn plan_fixture(root: &Path) {
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::write(root.join("a/b/file"), b"hello").unwrap();
+    fs::write(root.join("top"), b"abc").unwrap();
+    std::os::unix::fs::symlink("a", root.join("link")).unwrap();
+}
+
+#[cfg(not(windows))]
+#[test]
+fn plan_counts_without_removing() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    plan_fixture(&path);
+
+    let mut dir = fs::File::open(&path).unwrap();
+    let plan = remove_dir_all::plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap();
+    assert_eq!(plan.files(), 2);
+    assert_eq!(plan.dirs(), 2);
+    assert_eq!(plan.symlinks(), 1);
+    assert_eq!(plan.total_bytes(), 8);
+    assert_exists(&path.join("a/b/file"));
+
+    let mut order = Vec::new();
+    plan.for_each(|path, _entry| order.push(path.to_string()));
+    let pos = |p: &str| order.iter().position(|o| o == p).unwrap();
+    assert!(pos("a/b/file") < pos("a/b"));
+    assert!(pos("a/b") < pos("a"));
+
+    plan.execute(&mut dir, |s| Path::new(s)).unwrap();
+    assert_empty(&path);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn plan_refuses_new_entries() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    plan_fixture(&path);
+
+    let mut dir = fs::File::open(&path).unwrap();
+    let plan = remove_dir_all::plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap();
+    fs::write(path.join("a/b/new"), b"").unwrap();
+    plan.execute(&mut dir, |s| Path::new(s)).unwrap_err();
+    assert_exists(&path.join("a/b/new"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn plan_refuses_replaced_entries() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    plan_fixture(&path);
+
+    let mut dir = fs::File::open(&path).unwrap();
+    let plan = remove_dir_all::plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap();
+    // Swap the directory for a different one with the same contents.
+    fs::rename(path.join("a"), tempdir.path().join("moved")).unwrap();
+    plan_fixture(&tempdir.path().join("other"));
+    fs::rename(tempdir.path().join("other/a"), path.join("a")).unwrap();
+    plan.execute(&mut dir, |s| Path::new(s)).unwrap_err();
+    assert_exists(&path.join("a/b/file"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn plan_with_options() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    plan_fixture(&path);
+
+    let mut dir = fs::File::open(&path).unwrap();
+    let mut options = remove_dir_all::RemoveOptions::new();
+    options.one_file_system(true).max_open_dirs(2);
+    let plan = options.plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap();
+    assert_eq!(plan.dirs(), 2);
+    assert!(plan.is_complete());
+
+    let token = remove_dir_all::CancellationToken::new();
+    token.cancel();
+    options.cancellation_token(token);
+    let e = options.plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap_err();
+    assert!(remove_dir_all::CancellationToken::is_cancellation(&e));
+    assert_exists(&path.join("a/b/file"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn plan_refuses_replaced_empty_directory() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    plan_fixture(&path);
+
+    let mut dir = fs::File::open(&path).unwrap();
+    let plan = remove_dir_all::plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap();
+    // Swap `a/b` for another empty directory once its file is gone.
+    let b = path.join("a/b");
+    let moved = tempdir.path().join("moved");
+    let mut options = remove_dir_all::RemoveOptions::new();
+    options.progress(move |progress| {
+        if progress.path().to_string().ends_with("b/file") {
+            fs::rename(&b, &moved).unwrap();
+            fs::create_dir(&b).unwrap();
+        }
+    });
+    options.execute(&plan, &mut dir, |s| Path::new(s)).unwrap_err();
+    assert_exists(&path.join("a/b"));
+    assert_not_found!(&path.join("a/b/file"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn plan_deep_tree() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("deep");
+    // Far deeper than the default limit of 64 open directories.
+    let mut leaf = path.clone();
+    for _ in 0..600 {
+        leaf.push("d");
+    }
+    fs::create_dir_all(&leaf).unwrap();
+    fs::write(leaf.join("file"), b"x").unwrap();
+
+    let mut dir = fs::File::open(&path).unwrap();
+    let plan = remove_dir_all::plan_remove_dir_all(&mut dir, |s| Path::new(s)).unwrap();
+    assert_eq!(plan.dirs(), 600);
+    assert_eq!(plan.files(), 1);
+    assert_eq!(plan.total_bytes(), 1);
+    plan.execute(&mut dir, |s| Path::new(s)).unwrap();
+    assert_empty(&path);
+}
+
+// RemoveOptions
+
+#[test]
+fn options_report_progress() {
+    use std::sync::{Arc, Mutex};
+
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    fs::create_dir_all(path.join("a/b")).unwrap();
+    fs::write(path.join("a/b/file"), b"hello").unwrap();
+    fs::write(path.join("top"), b"abc").unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorder = seen.clone();
+    remove_dir_all::RemoveOptions::new()
+        .progress(move |p| {
+            recorder
+                .lock()
+                .unwrap()
+                .push((p.entries(), p.bytes(), p.path().to_string()))
+        })
+        .remove_dir_all(&path, |s| Path::new(s))
+        .unwrap();
+    assert_not_found!(&path);
+
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort();
+    // a/b/file, a/b, a, top and the root itself
+    assert_eq!(seen.len(), 5);
+    assert_eq!(seen.last().unwrap().0, 5);
+    assert_eq!(seen.iter().map(|s| s.1).max(), Some(8));
+    assert!(seen.iter().any(|s| s.2.ends_with("a/b/file")));
+}
+
+#[test]
+fn options_cancellation() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    fs::create_dir_all(&path).unwrap();
+    for i in 0..5 {
+        fs::write(path.join(format!("file-{i}")), b"").unwrap();
+    }
+
+    let token = remove_dir_all::CancellationToken::new();
+    let canceller = token.clone();
+    let err = remove_dir_all::RemoveOptions::new()
+        .progress(move |_| canceller.cancel())
+        .cancellation_token(token.clone())
+        .remove_dir_all(&path, |s| Path::new(s))
+        .unwrap_err();
+    assert!(remove_dir_all::CancellationToken::is_cancellation(&err));
+    assert!(token.is_cancelled());
+    assert_exists(&path);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn one_file_system_refuses_bind_mounts() {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    let source = tempdir.path().join("source");
+    fs::create_dir_all(path.join("mnt")).unwrap();
+    fs::create_dir(&source).unwrap();
+    fs::write(source.join("precious"), b"x").unwrap();
+
+    let c_path = |p: &Path| CString::new(p.as_os_str().as_bytes()).unwrap();
+    let (source_c, target_c) = (c_path(&source), c_path(&path.join("mnt")));
+    let (src, dst) = (source_c.as_ptr(), target_c.as_ptr());
+    if unsafe { libc::mount(src, dst, std::ptr::null(), libc::MS_BIND, std::ptr::null()) } != 0 {
+        // Mounting needs CAP_SYS_ADMIN.
+        return;
+    }
+    let result = remove_dir_all::RemoveOptions::new()
+        .one_file_system(true)
+        .remove_dir_all(&path, |s| Path::new(s));
+    unsafe { libc::umount(dst) };
+    let err = result.unwrap_err();
+    assert!(remove_dir_all::RemoveOptions::is_other_file_system(&err));
+    assert_exists(&source.join("precious"));
+}
+
+#[test]
+fn removes_deep_tree_within_fd_budget() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("deep");
+    let mut leaf = path.clone();
+    for i in 0..300 {
+        leaf.push("d");
+        if i % 50 == 0 {
+            fs::create_dir_all(&leaf).unwrap();
+            fs::write(leaf.join("file"), b"x").unwrap();
+            fs::create_dir(leaf.join("sibling")).unwrap();
+        }
+    }
+    fs::create_dir_all(&leaf).unwrap();
+
+    remove_dir_all::RemoveOptions::new()
+        .max_open_dirs(3)
+        .remove_dir_all(&path, |s| Path::new(s))
+        .unwrap();
+    assert_not_found!(&path);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn refuses_swapped_directory() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    fs::create_dir_all(path.join("a")).unwrap();
+    fs::write(path.join("a/file"), b"").unwrap();
+
+    let a = path.join("a");
+    let moved = tempdir.path().join("moved");
+    let swap_moved = moved.clone();
+    let err = remove_dir_all::RemoveOptions::new()
+        .progress(move |p| {
+            if p.path().to_string().ends_with("a/file") {
+                // Swap the emptied directory for a different empty one.
+                fs::rename(&a, &swap_moved).unwrap();
+                fs::create_dir(&a).unwrap();
+            }
+        })
+        .remove_dir_all(&path, |s| Path::new(s))
+        .unwrap_err();
+    assert!(remove_dir_all::RemoveOptions::is_directory_replaced(&err));
+    assert_exists(&path.join("a"));
+    assert_exists(&moved);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn fix_permissions_read_only_tree() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    // Permissions don't stop root, so the fix-ups would never be exercised.
+    if unsafe { libc::geteuid() } == 0 {
+        log::warn!("skipping fix_permissions_read_only_tree: running as root");
+        return;
+    }
+
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("root");
+    let outside = tempdir.path().join("outside");
+    fs::create_dir_all(path.join("a/b")).unwrap();
+    fs::create_dir_all(path.join("unreadable")).unwrap();
+    fs::write(path.join("a/b/file"), b"x").unwrap();
+    fs::create_dir(&outside).unwrap();
+    symlink(&outside, path.join("a/link")).unwrap();
+    let mode = |p: &Path| fs::symlink_metadata(p).unwrap().permissions().mode() & 0o7777;
+    let set_mode = |p: &Path, m| fs::set_permissions(p, fs::Permissions::from_mode(m)).unwrap();
+    set_mode(&outside, 0o500);
+    set_mode(&path.join("a/b"), 0o500);
+    set_mode(&path.join("a"), 0o500);
+    set_mode(&path.join("unreadable"), 0o000);
+
+    remove_dir_all::RemoveOptions::new()
+        .fix_permissions(true)
+        .remove_dir_all(&path, |s| Path::new(s))
+        .unwrap();
+    assert_not_found!(&path);
+    assert_eq!(mode(&outside), 0o500);
+    set_mode(&outside, 0o700);
+}
+
+#[test]
+fn deferred_removes_in_background() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("target");
+    fs::create_dir_all(path.join("a/b")).unwrap();
+    fs::write(path.join("a/b/file"), b"x").unwrap();
+
+    let removal = remove_dir_all::remove_dir_all_deferred(&path, |s| Path::new(s)).unwrap();
+    assert_not_found!(&path);
+    assert_eq!(removal.trash().len(), 1);
+    let trash = removal.trash()[0].clone();
+    assert_eq!(trash.parent(), Some(tempdir.path()));
+    removal.wait().unwrap();
+    assert_not_found!(&trash);
+    assert_empty(tempdir.path());
+}
+
+#[test]
+fn deferred_cancel_and_resume() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("target");
+    fs::create_dir_all(path.join("a")).unwrap();
+    fs::write(path.join("a/file"), b"x").unwrap();
+    // Left behind by a process that no longer exists, and by one that does.
+    let crashed = tempdir.path().join(".remove_dir_all-trash.999999999.0");
+    fs::create_dir_all(crashed.join("a")).unwrap();
+    let running = tempdir
+        .path()
+        .join(format!(".remove_dir_all-trash.{}.0", std::process::id()));
+    fs::create_dir(&running).unwrap();
+    let other = tempdir.path().join("unrelated");
+    fs::create_dir(&other).unwrap();
+
+    let token = remove_dir_all::CancellationToken::new();
+    token.cancel();
+    let err = remove_dir_all::RemoveOptions::new()
+        .cancellation_token(token)
+        .remove_dir_all_deferred(&path, |s| Path::new(s))
+        .unwrap()
+        .wait()
+        .unwrap_err();
+    assert!(remove_dir_all::CancellationToken::is_cancellation(&err));
+    assert_not_found!(&path);
+
+    let removal = remove_dir_all::resume_deferred(tempdir.path(), |s| Path::new(s)).unwrap();
+    // The cancelled trash, the crashed one, and the one of this process that
+    // nothing is removing.
+    assert_eq!(removal.trash().len(), 3);
+    removal.wait().unwrap();
+    assert_exists(&other);
+    assert_eq!(fs::read_dir(tempdir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn deferred_refuses_symlinks() {
+    let tempdir = TempDir::new().unwrap();
+    let dir = tempdir.path().join("dir");
+    fs::create_dir(&dir).unwrap();
+    let link = tempdir.path().join("link");
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, &link).unwrap();
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(&dir, &link).unwrap();
+
+    remove_dir_all::remove_dir_all_deferred(&link, |s| Path::new(s)).unwrap_err();
+    assert_exists(&dir);
+}
+
+#[cfg(all(feature = "fault-injection", not(windows)))]
+mod fault_injection {
+    use std::{fs, io::ErrorKind, path::Path};
+
+    use remove_dir_all::{Fault, FaultOp, FaultyIo, RemoveOptions};
+    use tempfile::TempDir;
+    use test_log::test;
+
+    use super::assert_exists;
+
+    fn fixture(tempdir: &TempDir) -> std::path::PathBuf {
+        let path = tempdir.path().join("root");
+        fs::create_dir_all(path.join("a/b")).unwrap();
+        fs::write(path.join("a/file"), b"x").unwrap();
+        fs::write(path.join("a/b/locked"), b"x").unwrap();
+        path
+    }
+
+    #[test]
+    fn permission_denied() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject("locked", FaultOp::Unlink, Fault::PermissionDenied);
+
+        let err = RemoveOptions::new()
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(io.injected(), 1);
+        assert_exists(&path.join("a/b/locked"));
+    }
+
+    #[test]
+    fn busy_directory() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject("b", FaultOp::Rmdir, Fault::Busy);
+
+        let err = RemoveOptions::new()
+            .remove_dir_contents_with(&io, &path, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBUSY));
+        super::assert_empty(&path.join("a/b"));
+    }
+
+    #[test]
+    fn busy_root() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject("root", FaultOp::Rmdir, Fault::Busy);
+
+        let err = RemoveOptions::new()
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBUSY));
+        super::assert_empty(&path);
+    }
+
+    #[test]
+    fn not_empty_race() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject_once("b", FaultOp::Rmdir, Fault::NotEmptyRace);
+
+        let err = RemoveOptions::new()
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTEMPTY));
+        assert_exists(&path.join("a/b/intruder"));
+
+        // The fault was transient: trying again succeeds.
+        RemoveOptions::new()
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap();
+        assert_not_found!(&path);
+    }
+
+    #[test]
+    fn symlink_swap_is_not_followed() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let outside = tempdir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("precious"), b"x").unwrap();
+        let mut io = FaultyIo::new();
+        io.inject_once("b", FaultOp::Open, Fault::SymlinkSwap(outside.clone()));
+
+        // Whether the scan in progress also sees `b.swapped` is unspecified,
+        // so the outcome of the removal is too.
+        let _ = RemoveOptions::new().remove_dir_all_with(&io, &path, |s| Path::new(s));
+        assert_eq!(io.injected(), 1);
+        assert_exists(&outside.join("precious"));
+        assert!(fs::symlink_metadata(path.join("a/b")).is_err());
+    }
+
+    #[test]
+    fn fix_permissions_retries() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject_once("locked", FaultOp::Unlink, Fault::PermissionDenied)
+            .inject_once("b", FaultOp::Open, Fault::PermissionDenied);
+
+        RemoveOptions::new()
+            .fix_permissions(true)
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap();
+        assert_eq!(io.injected(), 2);
+        assert_not_found!(&path);
+    }
+
+    #[test]
+    fn fix_permissions_retries_once() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject("locked", FaultOp::Unlink, Fault::PermissionDenied);
+
+        let err = RemoveOptions::new()
+            .fix_permissions(true)
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(io.injected(), 2);
+    }
+
+    #[test]
+    fn plan_and_execute() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject_once("b", FaultOp::Open, Fault::PermissionDenied)
+            .inject_once("locked", FaultOp::Unlink, Fault::PermissionDenied);
+
+        let options = RemoveOptions::new();
+        let mut dir = fs::File::open(&path).unwrap();
+        let err = options
+            .plan_remove_dir_all_with(&io, &mut dir, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        let plan = options
+            .plan_remove_dir_all_with(&io, &mut dir, |s| Path::new(s))
+            .unwrap();
+        let err = options
+            .execute_with(&io, &plan, &mut dir, |s| Path::new(s))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(io.injected(), 2);
+        assert_exists(&path.join("a/b/locked"));
+    }
+
+    #[test]
+    fn eloop_on_directory() {
+        let tempdir = TempDir::new().unwrap();
+        let path = fixture(&tempdir);
+        let mut io = FaultyIo::new();
+        io.inject("b", FaultOp::Open, Fault::Loop);
+
+        // `b` is taken for a symlink, and unlinking a directory fails.
+        RemoveOptions::new()
+            .remove_dir_all_with(&io, &path, |s| Path::new(s))
+            .unwrap_err();
+        assert_exists(&path.join("a/b/locked"));
+    }
+}
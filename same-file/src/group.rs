@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::FileId;
+
+/// The outcome of adding a path to an [`IdentitySet`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Seen<'a> {
+    /// The path is the first one seen for its file.
+    First,
+    /// The path refers to the same file as the given path, seen earlier. This
+    /// is the case for hard links, symlinks and bind mount aliases.
+    SameFile(&'a Path),
+    /// The path refers to a different file than any seen earlier, but with
+    /// the same contents as the given path. This is only reported when
+    /// contents are confirmed, see [`IdentitySet::confirm_contents`].
+    SameContent(&'a Path),
+}
+
+impl<'a> Seen<'a> {
+    /// Returns true if the path was seen before, by identity or by contents.
+    pub fn is_duplicate(&self) -> bool {
+        !matches!(*self, Seen::First)
+    }
+
+    /// Returns the earlier path this path is a duplicate of, if any.
+    pub fn original(&self) -> Option<&'a Path> {
+        match *self {
+            Seen::First => None,
+            Seen::SameFile(p) | Seen::SameContent(p) => Some(p),
+        }
+    }
+}
+
+/// A set of files, keyed by their [`FileId`], that tells whether each path
+/// added to it refers to a file that was seen before.
+///
+/// Only the first path of each file is kept, and no file stays open, so the
+/// set can be used to detect hard links and aliases while walking large
+/// trees.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// use same_file::{IdentitySet, Seen};
+///
+/// # fn try_main() -> Result<(), Box<dyn Error>> {
+/// let mut set = IdentitySet::new();
+/// for path in &["./a", "./b", "./a-hardlink"] {
+///     if let Seen::SameFile(first) = set.insert(path)? {
+///         println!("{} is {}", path, first.display());
+///     }
+/// }
+/// #     Ok(())
+/// # }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct IdentitySet {
+    ids: HashMap<FileId, usize>,
+    firsts: Vec<PathBuf>,
+    // The indices of the first paths of regular files, by size. Only filled
+    // in when contents are confirmed.
+    by_size: HashMap<u64, Vec<usize>>,
+    confirm_contents: bool,
+}
+
+impl IdentitySet {
+    /// Creates an empty set.
+    pub fn new() -> IdentitySet {
+        IdentitySet::default()
+    }
+
+    /// Whether to also compare the contents of regular files that are not
+    /// the same file, to detect copies and reflinked clones.
+    ///
+    /// Each new regular file is then read and compared with every earlier
+    /// file of the same size, so this is much slower than comparing
+    /// identities alone. Files that are added before this is enabled are not
+    /// compared.
+    ///
+    /// This is disabled by default.
+    pub fn confirm_contents(&mut self, yes: bool) -> &mut IdentitySet {
+        self.confirm_contents = yes;
+        self
+    }
+
+    /// Adds a path to the set, and returns whether its file was seen before.
+    ///
+    /// Symlinks are followed.
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if the path cannot be
+    /// inspected, or when confirming contents, if a file cannot be read. The
+    /// set is left unchanged.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Seen<'_>> {
+        let (index, seen) = self.insert_index(path.as_ref())?;
+        let first = &self.firsts[index];
+        Ok(match seen {
+            None => Seen::First,
+            Some(Match::File) => Seen::SameFile(first),
+            Some(Match::Content) => Seen::SameContent(first),
+        })
+    }
+
+    /// Returns the number of paths that were seen first, i.e. the number of
+    /// distinct files, or of distinct contents when contents are confirmed.
+    pub fn len(&self) -> usize {
+        self.firsts.len()
+    }
+
+    /// Returns true if no path was added to the set.
+    pub fn is_empty(&self) -> bool {
+        self.firsts.is_empty()
+    }
+
+    /// Adds a path, and returns the index of the first path of its file and
+    /// how it matched, if it was not the first.
+    fn insert_index(
+        &mut self,
+        path: &Path,
+    ) -> io::Result<(usize, Option<Match>)> {
+        let id = FileId::from_path(path)?;
+        if let Some(&index) = self.ids.get(&id) {
+            return Ok((index, Some(Match::File)));
+        }
+        let mut size = None;
+        if self.confirm_contents {
+            let md = fs::metadata(path)?;
+            if md.is_file() {
+                size = Some(md.len());
+                for &index in self.by_size.get(&md.len()).into_iter().flatten()
+                {
+                    // The file itself is not recorded, so that its other
+                    // names are compared by contents too.
+                    if same_contents(&self.firsts[index], path)? {
+                        return Ok((index, Some(Match::Content)));
+                    }
+                }
+            }
+        }
+        let index = self.firsts.len();
+        self.ids.insert(id, index);
+        self.firsts.push(path.to_path_buf());
+        if let Some(size) = size {
+            self.by_size.entry(size).or_default().push(index);
+        }
+        Ok((index, None))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Match {
+    File,
+    Content,
+}
+
+/// Groups paths by the file they refer to.
+///
+/// Each group holds the paths of one file, in the order they were given, and
+/// groups are in the order their first path was given. Symlinks are followed.
+/// To group files with the same contents too, use an [`IdentitySet`] with
+/// [`IdentitySet::confirm_contents`] and [`group_with`].
+///
+/// # Errors
+/// This function will return an [`io::Error`] if any of the paths cannot be
+/// inspected. The most common reasons for this are: the path does not exist,
+/// or there were not enough permissions.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use same_file::group_by_identity;
+///
+/// let groups = group_by_identity(&["./a", "./b", "./a-hardlink"]).unwrap();
+/// for group in groups.iter().filter(|g| g.len() > 1) {
+///     println!("hard links: {:?}", group);
+/// }
+/// ```
+pub fn group_by_identity<I>(paths: I) -> io::Result<Vec<Vec<PathBuf>>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    group_with(&mut IdentitySet::new(), paths)
+}
+
+/// Groups paths like [`group_by_identity`], using the given set to tell
+/// files apart.
+///
+/// Paths of files already in the set when this is called are grouped with
+/// the new paths of the same file, but are not themselves part of the result.
+///
+/// # Errors
+/// This function will return an [`io::Error`] if any of the paths cannot be
+/// inspected, or when confirming contents, if a file cannot be read.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+pub fn group_with<I>(
+    set: &mut IdentitySet,
+    paths: I,
+) -> io::Result<Vec<Vec<PathBuf>>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut groups: Vec<Vec<PathBuf>> = vec![];
+    let mut group_of = HashMap::new();
+    for path in paths {
+        let path = path.as_ref();
+        let (index, _) = set.insert_index(path)?;
+        let group = *group_of.entry(index).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(path.to_path_buf());
+    }
+    Ok(groups)
+}
+
+/// Returns true if the files at two paths have the same contents.
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let (mut abuf, mut bbuf) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let n = read_full(&mut a, &mut abuf)?;
+        if n != read_full(&mut b, &mut bbuf)? || abuf[..n] != bbuf[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Reads until `buf` is full or the end of the file is reached.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
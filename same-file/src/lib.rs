@@ -59,8 +59,16 @@ See [`examples/is_stderr.rs`] for a runnable example and compare the output of:
 - `cargo run --example is_stderr 2> examples/stderr` and
 - `cargo run --example is_stderr`.
 
+To find out which of many paths refer to the same file, as when detecting
+hard links or loops in a directory walk, use [`group_by_identity`] or an
+[`IdentitySet`]. These key files by [`FileId`], which, unlike a handle, does
+not keep the file open.
+
 [`is_same_file`]: fn.is_same_file.html
 [`Handle`]: struct.Handle.html
+[`group_by_identity`]: fn.group_by_identity.html
+[`IdentitySet`]: struct.IdentitySet.html
+[`FileId`]: struct.FileId.html
 [`examples/is_stderr.rs`]: https://github.com/BurntSushi/same-file/blob/master/examples/is_same_file.rs
 
 */
@@ -71,10 +79,16 @@ See [`examples/is_stderr.rs`] for a runnable example and compare the output of:
 #[cfg(doctest)]
 doc_comment::doctest!("../README.md");
 
-use std::fs::File;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::path::Path;
 
+#[cfg(any(target_os = "redox", unix))]
+use std::os::unix::io::AsFd;
+
+pub use crate::group::{group_by_identity, group_with, IdentitySet, Seen};
+
 #[cfg(any(target_os = "redox", unix))]
 use crate::unix as imp;
 #[cfg(not(any(target_os = "redox", unix, windows)))]
@@ -82,6 +96,9 @@ use unknown as imp;
 #[cfg(windows)]
 use win as imp;
 
+mod group;
+#[cfg(all(feature = "strong-identity", target_os = "linux"))]
+mod linux;
 #[cfg(any(target_os = "redox", unix))]
 mod unix;
 #[cfg(not(any(target_os = "redox", unix, windows)))]
@@ -183,6 +200,62 @@ impl Handle {
         imp::Handle::from_file(file).map(Handle)
     }
 
+    /// Construct a handle from the entry `name` of the directory `dir`.
+    ///
+    /// The entry is opened in read-only mode with `openat`, so that only the
+    /// directory needs to be accessible, not any path to it. `name` must be
+    /// the name of an entry directly in `dir`: paths with more than one
+    /// component, `..` or a root are rejected, since they could reach outside
+    /// of `dir`. If `follow` is false and the entry is a symlink, opening it
+    /// fails, as with `O_NOFOLLOW`.
+    ///
+    /// If `follow` is true and the entry is a symlink, its target is resolved
+    /// like any other path: an absolute target, or one containing `..`, opens
+    /// a file outside of `dir`. Pass `false` when `dir` is meant to confine
+    /// the lookup.
+    ///
+    /// Note that this only works on unix platforms.
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if `name` is not the name of
+    /// a directory entry, if the entry cannot be opened, or if `follow` is
+    /// false and the entry is a symlink.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use std::error::Error;
+    /// # use std::fs::File;
+    /// use same_file::Handle;
+    ///
+    /// # fn try_main() -> Result<(), Box<dyn Error>> {
+    /// let dir = File::open("./granted")?;
+    /// let source = Handle::from_fd_at(&dir, "source", false)?;
+    /// let target = Handle::from_fd_at(&dir, "target", false)?;
+    /// assert_ne!(source, target, "The files are the same.");
+    /// #     Ok(())
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #     try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg(any(target_os = "redox", unix))]
+    pub fn from_fd_at<D, P>(
+        dir: &D,
+        name: P,
+        follow: bool,
+    ) -> io::Result<Handle>
+    where
+        D: AsFd,
+        P: AsRef<Path>,
+    {
+        imp::Handle::from_fd_at(dir.as_fd(), name.as_ref(), follow)
+            .map(Handle)
+    }
+
     /// Construct a handle from stdin.
     ///
     /// # Errors
@@ -331,6 +404,57 @@ impl Handle {
         self.0.as_file_mut()
     }
 
+    /// Return the identity of the underlying file.
+    ///
+    /// Comparing handles with `==` only compares the cheap part of the
+    /// identity that is computed when the handle is created. This computes the
+    /// full identity, which with the `strong-identity` feature on Linux
+    /// includes the mount ID and file handle of the file. See [`Identity`]
+    /// for details.
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if the identity of the file
+    /// cannot be obtained, for example for a console on Windows.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use std::error::Error;
+    /// use same_file::Handle;
+    ///
+    /// # fn try_main() -> Result<(), Box<dyn Error>> {
+    /// let a = Handle::from_path("./a")?.identity()?;
+    /// let b = Handle::from_path("./b")?.identity()?;
+    /// if a == b {
+    ///     println!("same file (mount ID {:?})", a.mount_id());
+    /// }
+    /// #     Ok(())
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #     try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn identity(&self) -> io::Result<Identity> {
+        self.0.identity()
+    }
+
+    /// Return the [`FileId`] of the underlying file.
+    ///
+    /// The returned key can be kept after the handle is dropped, which closes
+    /// the file.
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if the identity of the file
+    /// cannot be obtained, for example for a console on Windows.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    pub fn file_id(&self) -> io::Result<FileId> {
+        self.0.file_id()
+    }
+
     /// Return the underlying device number of this handle.
     ///
     /// Note that this only works on unix platforms.
@@ -348,6 +472,202 @@ impl Handle {
     }
 }
 
+/// The identity of a file, as returned by [`Handle::identity`].
+///
+/// An identity is made of:
+///
+/// * the device number and inode number of the file on Unix, or the volume
+///   serial number and file index on Windows. These are what handles are
+///   compared by.
+/// * with the `strong-identity` feature on Linux, the ID of the mount the
+///   file was opened on (from `statx` with `STATX_MNT_ID`, Linux 5.8 or
+///   newer), and the file handle of the file (from `name_to_handle_at`), if
+///   its file system supports them. File handles tell apart files that share
+///   an inode number, as can happen on some FUSE file systems.
+///
+/// Components that are not available are `None`.
+///
+/// Two identities with a file handle are equal if they have the same device
+/// number, which identifies the file system, and the same file handle type
+/// and bytes; their inode numbers are not compared. Identities without a file
+/// handle are equal if they have the same device and inode number. The mount
+/// ID is never compared, so that a file seen through two bind mounts has the
+/// same identity.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    id: FileId,
+    mount_id: Option<u64>,
+    file_handle: Option<(i32, Vec<u8>)>,
+}
+
+impl Eq for Identity {}
+
+impl PartialEq for Identity {
+    fn eq(&self, other: &Identity) -> bool {
+        match (&self.file_handle, &other.file_handle) {
+            (Some(a), Some(b)) => self.id.dev == other.id.dev && a == b,
+            (None, None) => self.id == other.id,
+            _ => false,
+        }
+    }
+}
+
+impl Hash for Identity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.file_handle {
+            Some(handle) => {
+                self.id.dev.hash(state);
+                handle.hash(state);
+            }
+            None => self.id.hash(state),
+        }
+    }
+}
+
+impl Identity {
+    /// Return the device number, or the volume serial number on Windows.
+    pub fn dev(&self) -> u64 {
+        self.id.dev
+    }
+
+    /// Return the inode number, or the file index on Windows.
+    pub fn ino(&self) -> u64 {
+        self.id.ino
+    }
+
+    /// Return the [`FileId`] part of the identity.
+    pub fn file_id(&self) -> FileId {
+        self.id
+    }
+
+    /// Return the ID of the mount the file was opened on, if known.
+    ///
+    /// Mount IDs are only unique among the mounts that exist at the same
+    /// time.
+    pub fn mount_id(&self) -> Option<u64> {
+        self.mount_id
+    }
+
+    /// Return the type and opaque bytes of the kernel file handle of the
+    /// file, if known.
+    pub fn file_handle(&self) -> Option<(i32, &[u8])> {
+        self.file_handle.as_ref().map(|(ty, bytes)| (*ty, &bytes[..]))
+    }
+}
+
+/// A key identifying a file, that does not keep the file open.
+///
+/// Unlike a [`Handle`], which holds an open file for as long as it exists, a
+/// `FileId` is a plain value: it is `Copy`, can be stored in a `HashSet` of
+/// millions of entries without running out of file descriptors, and with the
+/// `serde` feature can be serialized to be compared in another process.
+///
+/// A `FileId` is the device number and inode number of a file on Unix, and
+/// the volume serial number and file index on Windows. Two keys are equal if
+/// they were obtained from the same file, with the same caveats as for
+/// handles.
+///
+/// Because the file is not kept open, its key is only meaningful while the
+/// file exists: once a file is deleted, a new file can be given the same
+/// inode number or file index. Keys should also not be compared across
+/// reboots, or after the file system was unmounted, since device numbers are
+/// not stable across those.
+///
+/// # Examples
+/// Skip directories that were visited already, as when following symlinks:
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// use std::collections::HashSet;
+/// use same_file::FileId;
+///
+/// # fn try_main() -> Result<(), Box<dyn Error>> {
+/// let mut visited = HashSet::new();
+/// for entry in std::fs::read_dir(".")? {
+///     let entry = entry?;
+///     if !visited.insert(FileId::from_dir_entry(&entry)?) {
+///         println!("{:?} was seen already", entry.path());
+///     }
+/// }
+/// #     Ok(())
+/// # }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileId {
+    dev: u64,
+    ino: u64,
+}
+
+impl FileId {
+    /// Return the key of the file at a path, following symlinks.
+    ///
+    /// On Unix this only calls `stat`. On Windows the file is opened while
+    /// its information is read, and closed again.
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if the metadata of the path
+    /// cannot be obtained. The most common reasons for this are: the path
+    /// does not exist, or there were not enough permissions.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    pub fn from_path<P: AsRef<Path>>(p: P) -> io::Result<FileId> {
+        imp::file_id_from_path(p.as_ref())
+    }
+
+    /// Return the key of an open file.
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if the metadata for the
+    /// given [`File`] cannot be obtained.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    /// [`File`]: https://doc.rust-lang.org/std/fs/struct.File.html
+    pub fn from_file(file: &File) -> io::Result<FileId> {
+        imp::file_id_from_file(file)
+    }
+
+    /// Return the key of the file a directory entry refers to, following
+    /// symlinks like [`FileId::from_path`].
+    ///
+    /// On Unix this reuses the metadata read for the entry where the platform
+    /// provides it. On Windows the entry is opened, as for
+    /// [`FileId::from_path`].
+    ///
+    /// # Errors
+    /// This method will return an [`io::Error`] if the metadata of the entry
+    /// cannot be obtained, for example because it was removed since the
+    /// directory was read.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    pub fn from_dir_entry(entry: &fs::DirEntry) -> io::Result<FileId> {
+        imp::file_id_from_dir_entry(entry)
+    }
+
+    /// Return the key of the file that metadata was obtained for.
+    ///
+    /// Note that this only works on unix platforms, since the metadata of a
+    /// file does not include its file index on Windows.
+    #[cfg(any(target_os = "redox", unix))]
+    pub fn from_metadata(md: &fs::Metadata) -> FileId {
+        imp::file_id_from_metadata(md)
+    }
+
+    /// Return the device number, or the volume serial number on Windows.
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
+
+    /// Return the inode number, or the file index on Windows.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+}
+
 /// Returns true if the two file paths may correspond to the same file.
 ///
 /// Note that it's possible for this to produce a false positive on some
@@ -375,6 +695,54 @@ where
     Ok(Handle::from_path(path1)? == Handle::from_path(path2)?)
 }
 
+/// Returns true if the entry `name1` of the directory `dir1` may be the same
+/// file as the entry `name2` of the directory `dir2`.
+///
+/// The entries are inspected with `fstatat`, so that only the directories
+/// need to be accessible, not any path to them. Like for
+/// [`Handle::from_fd_at`], each name must be the name of an entry directly
+/// in its directory. Symlinks are not followed: a symlink is only the same
+/// file as itself, so that the comparison never looks outside of the two
+/// directories.
+///
+/// Note that this only works on unix platforms, and that like
+/// [`is_same_file`], it can produce a false positive on some platforms.
+///
+/// # Errors
+/// This function will return an [`io::Error`] if either name is not the name
+/// of a directory entry, or if either entry cannot be inspected. The most
+/// common reasons for this are: the entry does not exist, or there were not
+/// enough permissions.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use same_file::is_same_file_at;
+///
+/// let dir = File::open("./granted").unwrap();
+/// assert!(is_same_file_at(&dir, "foo", &dir, "foo").unwrap_or(false));
+/// ```
+#[cfg(any(target_os = "redox", unix))]
+pub fn is_same_file_at<D1, P1, D2, P2>(
+    dir1: &D1,
+    name1: P1,
+    dir2: &D2,
+    name2: P2,
+) -> io::Result<bool>
+where
+    D1: AsFd,
+    P1: AsRef<Path>,
+    D2: AsFd,
+    P2: AsRef<Path>,
+{
+    let id1 = imp::file_id_at(dir1.as_fd(), name1.as_ref(), false)?;
+    let id2 = imp::file_id_at(dir2.as_fd(), name2.as_ref(), false)?;
+    Ok(id1 == id2)
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
@@ -384,7 +752,10 @@ mod tests {
     use std::path::{Path, PathBuf};
     use std::result;
 
-    use super::is_same_file;
+    use super::{
+        group_by_identity, is_same_file, FileId, Handle, Identity,
+        IdentitySet, Seen,
+    };
 
     type Result<T> = result::Result<T, Box<dyn error::Error + Send + Sync>>;
 
@@ -558,6 +929,165 @@ mod tests {
         assert!(is_same_file(dir.join(Path::new("a")), dir.join(Path::new("alink"))).unwrap());
     }
 
+    #[test]
+    fn identity_hard() {
+        let tdir = tmpdir();
+        let dir = tdir.path();
+
+        File::create(dir.join(Path::new("a"))).unwrap();
+        File::create(dir.join(Path::new("b"))).unwrap();
+        fs::hard_link(dir.join(Path::new("a")), dir.join(Path::new("alink"))).unwrap();
+        let identity = |name: &str| {
+            Handle::from_path(dir.join(Path::new(name))).unwrap().identity().unwrap()
+        };
+        let a = identity("a");
+        assert_eq!(a, identity("alink"));
+        assert_ne!(a, identity("b"));
+        if cfg!(all(feature = "strong-identity", target_os = "linux")) {
+            assert!(a.mount_id().is_some());
+        } else {
+            assert_eq!(a.mount_id(), None);
+            assert_eq!(a.file_handle(), None);
+        }
+    }
+
+    #[test]
+    fn identity_compares_file_handles() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let identity = |ino, mount_id, handle: Option<&[u8]>| Identity {
+            id: FileId { dev: 1, ino },
+            mount_id,
+            file_handle: handle.map(|h| (1, h.to_vec())),
+        };
+        let hash = |identity: &Identity| {
+            let mut hasher = DefaultHasher::new();
+            identity.hash(&mut hasher);
+            hasher.finish()
+        };
+        // The same file through two bind mounts.
+        let a = identity(2, Some(10), Some(b"handle"));
+        let b = identity(2, Some(11), Some(b"handle"));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        // An inode number reused by another file.
+        assert_ne!(a, identity(2, Some(10), Some(b"other")));
+        assert_ne!(a, identity(2, Some(10), None));
+        assert_eq!(identity(2, Some(10), None), identity(2, None, None));
+        assert_ne!(identity(2, None, None), identity(3, None, None));
+    }
+
+    #[test]
+    fn file_id_hard_and_soft() {
+        let tdir = tmpdir();
+        let dir = tdir.path();
+
+        File::create(dir.join(Path::new("a"))).unwrap();
+        File::create(dir.join(Path::new("b"))).unwrap();
+        fs::hard_link(dir.join(Path::new("a")), dir.join(Path::new("alink"))).unwrap();
+        soft_link_file(dir.join(Path::new("a")), dir.join(Path::new("asoft"))).unwrap();
+        let a = FileId::from_path(dir.join(Path::new("a"))).unwrap();
+        assert_eq!(a, FileId::from_path(dir.join(Path::new("alink"))).unwrap());
+        assert_ne!(a, FileId::from_path(dir.join(Path::new("b"))).unwrap());
+
+        let file = File::open(dir.join(Path::new("asoft"))).unwrap();
+        assert_eq!(a, FileId::from_file(&file).unwrap());
+        assert_eq!(a, Handle::from_file(file).unwrap().file_id().unwrap());
+        for entry in fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            let id = FileId::from_dir_entry(&entry).unwrap();
+            assert_eq!(id == a, entry.file_name() != "b", "{:?}", entry.path());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn file_id_serde_round_trip() {
+        let tdir = tmpdir();
+        let dir = tdir.path();
+
+        File::create(dir.join(Path::new("a"))).unwrap();
+        File::create(dir.join(Path::new("b"))).unwrap();
+        let a = FileId::from_path(dir.join(Path::new("a"))).unwrap();
+        let bytes = bincode::serialize(&a).unwrap();
+
+        // As if received from another process.
+        let received: FileId = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(a, received);
+        let file = File::open(dir.join(Path::new("a"))).unwrap();
+        assert_eq!(received, FileId::from_file(&file).unwrap());
+        assert_ne!(received, FileId::from_path(dir.join(Path::new("b"))).unwrap());
+    }
+
+    #[test]
+    fn group_hard_links() {
+        let tdir = tmpdir();
+        let dir = tdir.path();
+
+        File::create(dir.join(Path::new("a"))).unwrap();
+        File::create(dir.join(Path::new("b"))).unwrap();
+        fs::hard_link(dir.join(Path::new("a")), dir.join(Path::new("alink"))).unwrap();
+        let paths: Vec<_> = ["a", "b", "alink"].iter().map(|p| dir.join(Path::new(p))).collect();
+        let groups = group_by_identity(&paths).unwrap();
+        assert_eq!(groups, vec![vec![paths[0].clone(), paths[2].clone()], vec![paths[1].clone()]]);
+        assert!(group_by_identity(&[dir.join(Path::new("missing"))]).is_err());
+    }
+
+    #[test]
+    fn identity_set_contents() {
+        let tdir = tmpdir();
+        let dir = tdir.path();
+
+        fs::write(dir.join(Path::new("a")), "same").unwrap();
+        fs::write(dir.join(Path::new("copy")), "same").unwrap();
+        fs::write(dir.join(Path::new("other")), "diff").unwrap();
+        fs::hard_link(dir.join(Path::new("a")), dir.join(Path::new("alink"))).unwrap();
+
+        let mut set = IdentitySet::new();
+        assert_eq!(set.insert(dir.join(Path::new("a"))).unwrap(), Seen::First);
+        assert_eq!(set.insert(dir.join(Path::new("copy"))).unwrap(), Seen::First);
+        let a = dir.join(Path::new("a"));
+        assert_eq!(set.insert(dir.join(Path::new("alink"))).unwrap(), Seen::SameFile(&a));
+        assert_eq!(set.len(), 2);
+
+        let mut set = IdentitySet::new();
+        set.confirm_contents(true);
+        assert_eq!(set.insert(dir.join(Path::new("a"))).unwrap(), Seen::First);
+        assert_eq!(set.insert(dir.join(Path::new("copy"))).unwrap(), Seen::SameContent(&a));
+        assert_eq!(set.insert(dir.join(Path::new("other"))).unwrap(), Seen::First);
+        assert_eq!(set.insert(dir.join(Path::new("alink"))).unwrap(), Seen::SameFile(&a));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn same_file_at() {
+        use super::is_same_file_at;
+
+        let tdir = tmpdir();
+        let dir = tdir.path();
+
+        fs::create_dir(dir.join(Path::new("d1"))).unwrap();
+        fs::create_dir(dir.join(Path::new("d2"))).unwrap();
+        File::create(dir.join(Path::new("d1/a"))).unwrap();
+        File::create(dir.join(Path::new("d1/b"))).unwrap();
+        fs::hard_link(dir.join(Path::new("d1/a")), dir.join(Path::new("d2/alink"))).unwrap();
+        soft_link_file(dir.join(Path::new("d1/a")), dir.join(Path::new("d2/asoft"))).unwrap();
+        let d1 = File::open(dir.join(Path::new("d1"))).unwrap();
+        let d2 = File::open(dir.join(Path::new("d2"))).unwrap();
+
+        assert!(is_same_file_at(&d1, "a", &d2, "alink").unwrap());
+        assert!(!is_same_file_at(&d1, "a", &d1, "b").unwrap());
+        assert!(!is_same_file_at(&d1, "a", &d2, "asoft").unwrap());
+        assert!(is_same_file_at(&d1, "../d1/a", &d1, "a").is_err());
+        assert!(is_same_file_at(&d1, dir.join(Path::new("d1/a")), &d1, "a").is_err());
+
+        let a = Handle::from_fd_at(&d1, "a", false).unwrap();
+        assert_eq!(a, Handle::from_fd_at(&d2, "asoft", true).unwrap());
+        assert!(Handle::from_fd_at(&d2, "asoft", false).is_err());
+    }
+
     #[test]
     fn test_send() {
         fn assert_send<T: Send>() {}
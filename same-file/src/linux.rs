@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use rustix::fs::{statx, AtFlags, StatxFlags};
+
+// The inode number alone is not always enough to tell files apart. FUSE file
+// systems may reuse inode numbers, and overlayfs and btrfs subvolumes present
+// files from several underlying file systems under one mount. The kernel's
+// own notion of a file's identity is the file handle returned by
+// `name_to_handle_at`, which together with the mount it was obtained on
+// identifies a file without relying on inode numbers.
+//
+// Both are optional: `STATX_MNT_ID` needs Linux 5.8, and not every file system
+// can produce file handles (procfs and pipes can't, for example). Where they
+// are missing, the identity falls back to the device and inode number.
+
+/// `MAX_HANDLE_SZ` in the kernel.
+const MAX_HANDLE_SZ: usize = 128;
+
+/// `struct file_handle`, with room for the largest handle.
+#[repr(C)]
+struct FileHandle {
+    handle_bytes: u32,
+    handle_type: i32,
+    f_handle: [u8; MAX_HANDLE_SZ],
+}
+
+/// Returns the ID of the mount `file` was opened on, if the kernel reports
+/// it.
+pub fn mount_id(file: &File) -> io::Result<Option<u64>> {
+    match statx(file, "", AtFlags::EMPTY_PATH, StatxFlags::MNT_ID) {
+        Ok(stx) if stx.stx_mask & StatxFlags::MNT_ID.bits() != 0 => {
+            Ok(Some(stx.stx_mnt_id))
+        }
+        Ok(_) | Err(rustix::io::Errno::NOSYS) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns the type and bytes of the file handle of `file`, if its file
+/// system supports them.
+pub fn file_handle(file: &File) -> io::Result<Option<(i32, Vec<u8>)>> {
+    let mut handle = FileHandle {
+        handle_bytes: MAX_HANDLE_SZ as u32,
+        handle_type: 0,
+        f_handle: [0; MAX_HANDLE_SZ],
+    };
+    let mut mount_id: i32 = 0;
+    // rustix does not wrap name_to_handle_at.
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_name_to_handle_at,
+            file.as_raw_fd(),
+            b"\0".as_ptr(),
+            &mut handle as *mut FileHandle,
+            &mut mount_id as *mut i32,
+            libc::AT_EMPTY_PATH,
+        )
+    };
+    if rc == -1 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::EOPNOTSUPP)
+            | Some(libc::ENOSYS)
+            | Some(libc::EOVERFLOW) => Ok(None),
+            _ => Err(err),
+        };
+    }
+    let len = (handle.handle_bytes as usize).min(MAX_HANDLE_SZ);
+    Ok(Some((handle.handle_type, handle.f_handle[..len].to_vec())))
+}
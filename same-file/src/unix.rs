@@ -1,9 +1,12 @@
-use std::fs::{File, OpenOptions};
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
 use std::hash::{Hash, Hasher};
 use std::io;
 use std::os::unix::fs::MetadataExt;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
-use std::path::Path;
+use std::os::unix::io::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::path::{Component, Path};
+
+use rustix::fs::{openat, statat, AtFlags, Mode, OFlags};
 
 #[derive(Debug)]
 pub struct Handle {
@@ -11,8 +14,7 @@ pub struct Handle {
     // If is_std is true, then we don't drop the corresponding File since it
     // will close the handle.
     is_std: bool,
-    dev: u64,
-    ino: u64,
+    id: crate::FileId,
 }
 
 impl Drop for Handle {
@@ -29,7 +31,7 @@ impl Eq for Handle {}
 
 impl PartialEq for Handle {
     fn eq(&self, other: &Handle) -> bool {
-        (self.dev, self.ino) == (other.dev, other.ino)
+        self.id == other.id
     }
 }
 
@@ -51,8 +53,7 @@ impl IntoRawFd for crate::Handle {
 
 impl Hash for Handle {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.dev.hash(state);
-        self.ino.hash(state);
+        self.id.hash(state);
     }
 }
 
@@ -61,14 +62,23 @@ impl Handle {
         Handle::from_file(OpenOptions::new().read(true).open(p)?)
     }
 
+    pub fn from_fd_at(
+        dir: BorrowedFd<'_>,
+        name: &Path,
+        follow: bool,
+    ) -> io::Result<Handle> {
+        let name = entry_name(name)?;
+        let mut flags = OFlags::RDONLY | OFlags::CLOEXEC;
+        if !follow {
+            flags |= OFlags::NOFOLLOW;
+        }
+        let fd = openat(dir, name, flags, Mode::empty())?;
+        Handle::from_file(File::from(fd))
+    }
+
     pub fn from_file(file: File) -> io::Result<Handle> {
-        let md = file.metadata()?;
-        Ok(Handle {
-            file: Some(file),
-            is_std: false,
-            dev: md.dev(),
-            ino: md.ino(),
-        })
+        let id = file_id_from_file(&file)?;
+        Ok(Handle { file: Some(file), is_std: false, id })
     }
 
     pub fn from_std(file: File) -> io::Result<Handle> {
@@ -102,11 +112,77 @@ impl Handle {
         self.file.as_mut().take().unwrap()
     }
 
+    pub fn identity(&self) -> io::Result<crate::Identity> {
+        #[cfg(all(feature = "strong-identity", target_os = "linux"))]
+        let (mount_id, file_handle) = (
+            crate::linux::mount_id(self.as_file())?,
+            crate::linux::file_handle(self.as_file())?,
+        );
+        #[cfg(not(all(feature = "strong-identity", target_os = "linux")))]
+        let (mount_id, file_handle) = (None, None);
+        Ok(crate::Identity { id: self.id, mount_id, file_handle })
+    }
+
+    pub fn file_id(&self) -> io::Result<crate::FileId> {
+        Ok(self.id)
+    }
+
     pub fn dev(&self) -> u64 {
-        self.dev
+        self.id.dev
     }
 
     pub fn ino(&self) -> u64 {
-        self.ino
+        self.id.ino
+    }
+}
+
+pub fn file_id_from_path(p: &Path) -> io::Result<crate::FileId> {
+    Ok(file_id_from_metadata(&fs::metadata(p)?))
+}
+
+pub fn file_id_from_file(file: &File) -> io::Result<crate::FileId> {
+    Ok(file_id_from_metadata(&file.metadata()?))
+}
+
+pub fn file_id_from_dir_entry(
+    entry: &fs::DirEntry,
+) -> io::Result<crate::FileId> {
+    // DirEntry::metadata does not follow symlinks, but handles do.
+    if entry.file_type()?.is_symlink() {
+        file_id_from_path(&entry.path())
+    } else {
+        Ok(file_id_from_metadata(&entry.metadata()?))
+    }
+}
+
+pub fn file_id_from_metadata(md: &fs::Metadata) -> crate::FileId {
+    crate::FileId { dev: md.dev(), ino: md.ino() }
+}
+
+pub fn file_id_at(
+    dir: BorrowedFd<'_>,
+    name: &Path,
+    follow: bool,
+) -> io::Result<crate::FileId> {
+    let name = entry_name(name)?;
+    let flags =
+        if follow { AtFlags::empty() } else { AtFlags::SYMLINK_NOFOLLOW };
+    let stat = statat(dir, name, flags)?;
+    // The types of these fields differ between platforms.
+    #[allow(clippy::unnecessary_cast)]
+    Ok(crate::FileId { dev: stat.st_dev as u64, ino: stat.st_ino as u64 })
+}
+
+// Only names of entries directly in the directory are accepted. With a path
+// of several components, `..` or an intermediate symlink could reach outside
+// of the directory, and an absolute path would ignore it altogether.
+fn entry_name(name: &Path) -> io::Result<&OsStr> {
+    let mut components = name.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => Ok(name),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not the name of a directory entry",
+        )),
     }
 }
@@ -38,6 +38,14 @@ impl Handle {
         error()
     }
 
+    pub fn identity(&self) -> io::Result<crate::Identity> {
+        error()
+    }
+
+    pub fn file_id(&self) -> io::Result<crate::FileId> {
+        error()
+    }
+
     pub fn as_file(&self) -> &File {
         unreachable!(ERROR_MESSAGE);
     }
@@ -47,6 +55,20 @@ impl Handle {
     }
 }
 
+pub fn file_id_from_path(_p: &Path) -> io::Result<crate::FileId> {
+    error()
+}
+
+pub fn file_id_from_file(_file: &File) -> io::Result<crate::FileId> {
+    error()
+}
+
+pub fn file_id_from_dir_entry(
+    _entry: &std::fs::DirEntry,
+) -> io::Result<crate::FileId> {
+    error()
+}
+
 fn error<T>() -> io::Result<T> {
     Err(io::Error::new(io::ErrorKind::Other, ERROR_MESSAGE))
 }
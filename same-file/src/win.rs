@@ -50,7 +50,7 @@ use winapi_util as winutil;
 #[derive(Debug)]
 pub struct Handle {
     kind: HandleKind,
-    key: Option<Key>,
+    key: Option<crate::FileId>,
 }
 
 #[derive(Debug)]
@@ -61,12 +61,6 @@ enum HandleKind {
     Borrowed(winutil::HandleRef),
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
-struct Key {
-    volume: u64,
-    index: u64,
-}
-
 impl Eq for Handle {}
 
 impl PartialEq for Handle {
@@ -109,19 +103,21 @@ impl Hash for Handle {
 impl Handle {
     pub fn from_path<P: AsRef<Path>>(p: P) -> io::Result<Handle> {
         let h = winutil::Handle::from_path_any(p)?;
-        let info = winutil::file::information(&h)?;
-        Ok(Handle::from_info(HandleKind::Owned(h), info))
+        let key = file_id_from_file(h.as_file())?;
+        Ok(Handle { kind: HandleKind::Owned(h), key: Some(key) })
     }
 
     pub fn from_file(file: File) -> io::Result<Handle> {
+        let key = file_id_from_file(&file)?;
         let h = winutil::Handle::from_file(file);
-        let info = winutil::file::information(&h)?;
-        Ok(Handle::from_info(HandleKind::Owned(h), info))
+        Ok(Handle { kind: HandleKind::Owned(h), key: Some(key) })
     }
 
     fn from_std_handle(h: winutil::HandleRef) -> io::Result<Handle> {
-        match winutil::file::information(&h) {
-            Ok(info) => Ok(Handle::from_info(HandleKind::Borrowed(h), info)),
+        match file_id_from_file(h.as_file()) {
+            Ok(key) => {
+                Ok(Handle { kind: HandleKind::Borrowed(h), key: Some(key) })
+            }
             // In a Windows console, if there is no pipe attached to a STD
             // handle, then GetFileInformationByHandle will return an error.
             // We don't really care. The only thing we care about is that
@@ -131,19 +127,6 @@ impl Handle {
         }
     }
 
-    fn from_info(
-        kind: HandleKind,
-        info: winutil::file::Information,
-    ) -> Handle {
-        Handle {
-            kind: kind,
-            key: Some(Key {
-                volume: info.volume_serial_number(),
-                index: info.file_index(),
-            }),
-        }
-    }
-
     pub fn stdin() -> io::Result<Handle> {
         Handle::from_std_handle(winutil::HandleRef::stdin())
     }
@@ -156,6 +139,23 @@ impl Handle {
         Handle::from_std_handle(winutil::HandleRef::stderr())
     }
 
+    pub fn identity(&self) -> io::Result<crate::Identity> {
+        Ok(crate::Identity {
+            id: self.file_id()?,
+            mount_id: None,
+            file_handle: None,
+        })
+    }
+
+    pub fn file_id(&self) -> io::Result<crate::FileId> {
+        self.key.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "no file information for this handle",
+            )
+        })
+    }
+
     pub fn as_file(&self) -> &File {
         match self.kind {
             HandleKind::Owned(ref h) => h.as_file(),
@@ -170,3 +170,22 @@ impl Handle {
         }
     }
 }
+
+pub fn file_id_from_path(p: &Path) -> io::Result<crate::FileId> {
+    file_id_from_file(winutil::Handle::from_path_any(p)?.as_file())
+}
+
+pub fn file_id_from_file(file: &File) -> io::Result<crate::FileId> {
+    let info = winutil::file::information(file)?;
+    Ok(crate::FileId {
+        dev: info.volume_serial_number(),
+        ino: info.file_index(),
+    })
+}
+
+pub fn file_id_from_dir_entry(
+    entry: &std::fs::DirEntry,
+) -> io::Result<crate::FileId> {
+    // The metadata of a directory entry has no file index on Windows.
+    file_id_from_path(&entry.path())
+}
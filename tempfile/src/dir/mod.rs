@@ -462,6 +462,38 @@ impl TempDir {
 
         result
     }
+
+    /// Closes and removes the temporary directory without blocking the async
+    /// runtime.
+    ///
+    /// This behaves like [`TempDir::close`], but the removal runs on tokio's
+    /// blocking thread pool. Requires the `tokio` feature.
+    ///
+    /// # Errors
+    ///
+    /// See [`TempDir::close`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use tempfile::TempDir;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let tmp_dir = TempDir::new(|dir, name| Path::new(dir).join(name))?;
+    /// tmp_dir.close_async().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn close_async(mut self) -> io::Result<()> {
+        let path = mem::replace(&mut self.path, PathBuf::new().into_boxed_path());
+
+        // Prevent the Drop impl from being called.
+        mem::forget(self);
+
+        crate::util::unblock(move || remove_dir_all(&path).with_err_path(|| &*path)).await
+    }
 }
 
 impl AsRef<Path> for TempDir {
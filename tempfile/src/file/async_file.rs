@@ -0,0 +1,453 @@
+use std::error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+use super::{imp, NamedTempFile, PathPersistError, TempPath};
+use crate::error::IoResultExt;
+use crate::util::unblock;
+use crate::Builder;
+
+/// A named temporary file backed by a [`tokio::fs::File`].
+///
+/// This is the asynchronous counterpart of [`NamedTempFile`]. Creating,
+/// persisting, reopening and closing the file run on tokio's blocking thread
+/// pool, and so does the removal of the file when it is dropped inside a
+/// runtime. Outside of a runtime, the file is removed on the dropping thread.
+///
+/// Requires the `tokio` feature.
+///
+/// # Security
+///
+/// See [the security][security] docs on `NamedTempFile`.
+///
+/// # Resource leaking
+///
+/// See [the resource leaking][resource-leaking] docs on `NamedTempFile`.
+/// Additionally, removals scheduled while the runtime is shutting down may
+/// never run.
+///
+/// [security]: struct.NamedTempFile.html#security
+/// [resource-leaking]: struct.NamedTempFile.html#resource-leaking
+pub struct AsyncNamedTempFile {
+    path: BackgroundPath,
+    file: tokio::fs::File,
+}
+
+/// A `TempPath` that is removed on the blocking thread pool when dropped.
+struct BackgroundPath(TempPath);
+
+impl BackgroundPath {
+    fn into_inner(self) -> TempPath {
+        // Prevent the Drop impl from being called.
+        let mut this = mem::ManuallyDrop::new(self);
+        mem::replace(&mut this.0, TempPath::new(PathBuf::new(), true))
+    }
+}
+
+impl Drop for BackgroundPath {
+    fn drop(&mut self) {
+        if self.0.keep {
+            return;
+        }
+        let path = mem::replace(&mut self.0, TempPath::new(PathBuf::new(), true));
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(move || drop(path));
+            }
+            Err(_) => drop(path),
+        }
+    }
+}
+
+impl fmt::Debug for AsyncNamedTempFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AsyncNamedTempFile({:?})", self.path.0)
+    }
+}
+
+impl AsRef<Path> for AsyncNamedTempFile {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl From<NamedTempFile> for AsyncNamedTempFile {
+    fn from(file: NamedTempFile) -> Self {
+        let (file, path) = file.into_parts();
+        AsyncNamedTempFile::from_parts(tokio::fs::File::from_std(file), path)
+    }
+}
+
+/// Error returned when persisting an [`AsyncNamedTempFile`] fails.
+pub struct AsyncPersistError {
+    /// The underlying IO error.
+    pub error: io::Error,
+    /// The temporary file that couldn't be persisted.
+    pub file: AsyncNamedTempFile,
+}
+
+impl fmt::Debug for AsyncPersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AsyncPersistError({:?})", self.error)
+    }
+}
+
+impl From<AsyncPersistError> for io::Error {
+    #[inline]
+    fn from(error: AsyncPersistError) -> io::Error {
+        error.error
+    }
+}
+
+impl From<AsyncPersistError> for AsyncNamedTempFile {
+    #[inline]
+    fn from(error: AsyncPersistError) -> AsyncNamedTempFile {
+        error.file
+    }
+}
+
+impl fmt::Display for AsyncPersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to persist temporary file: {}", self.error)
+    }
+}
+
+impl error::Error for AsyncPersistError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl AsyncNamedTempFile {
+    /// Create a new named temporary file.
+    ///
+    /// See [`Builder::tempfile_async`] for more configuration.
+    ///
+    /// # Errors
+    ///
+    /// If the file can not be created, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use tokio::io::AsyncWriteExt;
+    /// use tempfile::AsyncNamedTempFile;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let mut file = AsyncNamedTempFile::new(|dir, name| Path::new(dir).join(name)).await?;
+    /// file.write_all(b"Brian was here. Briefly.").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn new<J>(join: J) -> io::Result<AsyncNamedTempFile>
+    where
+        J: Fn(&OsStr, &OsStr) -> PathBuf + Send + 'static,
+    {
+        Builder::new().tempfile_async(join).await
+    }
+
+    /// Create a new named temporary file in the specified directory.
+    ///
+    /// See [`AsyncNamedTempFile::new`] for details.
+    pub async fn new_in<P, J>(dir: P, join: J) -> io::Result<AsyncNamedTempFile>
+    where
+        P: AsRef<Path>,
+        J: Fn(&OsStr, &OsStr) -> PathBuf + Send + 'static,
+    {
+        Builder::new().tempfile_in_async(dir, join).await
+    }
+
+    /// Get the temporary file's path.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path.0
+    }
+
+    /// Close and remove the temporary file.
+    ///
+    /// Use this if you want to detect errors in deleting the file.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be deleted, `Err` is returned.
+    pub async fn close(self) -> io::Result<()> {
+        let AsyncNamedTempFile { path, file } = self;
+        drop(file);
+        let path = path.into_inner();
+        unblock(move || path.close()).await
+    }
+
+    /// Persist the temporary file at the target path.
+    ///
+    /// See [`NamedTempFile::persist`] for details and security implications.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be moved to the new location, `Err` is returned.
+    pub async fn persist<P: AsRef<Path>>(
+        self,
+        new_path: P,
+    ) -> Result<tokio::fs::File, AsyncPersistError> {
+        let new_path = new_path.as_ref().to_path_buf();
+        self.persist_with(move |path| path.persist(new_path)).await
+    }
+
+    /// Persist the temporary file at the target path if and only if no file
+    /// exists there.
+    ///
+    /// See [`NamedTempFile::persist_noclobber`] for details and security
+    /// implications.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be moved to the new location or a file already
+    /// exists there, `Err` is returned.
+    pub async fn persist_noclobber<P: AsRef<Path>>(
+        self,
+        new_path: P,
+    ) -> Result<tokio::fs::File, AsyncPersistError> {
+        let new_path = new_path.as_ref().to_path_buf();
+        self.persist_with(move |path| path.persist_noclobber(new_path))
+            .await
+    }
+
+    /// Keep the temporary file from being deleted.
+    ///
+    /// See [`NamedTempFile::keep`] for details.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be marked as non-temporary, `Err` is returned.
+    pub async fn keep(self) -> Result<(tokio::fs::File, PathBuf), AsyncPersistError> {
+        let AsyncNamedTempFile { path, file } = self;
+        let path = path.into_inner();
+        match unblock(move || path.keep()).await {
+            Ok(path) => Ok((file, path)),
+            Err(PathPersistError { error, path }) => Err(AsyncPersistError {
+                file: AsyncNamedTempFile::from_parts(file, path),
+                error,
+            }),
+        }
+    }
+
+    async fn persist_with<G>(self, persist: G) -> Result<tokio::fs::File, AsyncPersistError>
+    where
+        G: FnOnce(TempPath) -> Result<(), PathPersistError> + Send + 'static,
+    {
+        let AsyncNamedTempFile { path, file } = self;
+        let path = path.into_inner();
+        match unblock(move || persist(path)).await {
+            Ok(()) => Ok(file),
+            Err(PathPersistError { error, path }) => Err(AsyncPersistError {
+                file: AsyncNamedTempFile::from_parts(file, path),
+                error,
+            }),
+        }
+    }
+
+    /// Securely reopen the temporary file.
+    ///
+    /// See [`NamedTempFile::reopen`] for details and security implications.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be reopened, `Err` is returned.
+    pub async fn reopen(&self) -> io::Result<tokio::fs::File> {
+        let file = self.file.try_clone().await?.into_std().await;
+        let path = self.path().to_path_buf();
+        unblock(move || imp::reopen(&file, &path))
+            .await
+            .map(tokio::fs::File::from_std)
+            .with_err_path(|| self.path())
+    }
+
+    /// Get a reference to the underlying file.
+    pub fn as_file(&self) -> &tokio::fs::File {
+        &self.file
+    }
+
+    /// Get a mutable reference to the underlying file.
+    pub fn as_file_mut(&mut self) -> &mut tokio::fs::File {
+        &mut self.file
+    }
+
+    /// Convert the temporary file into a `tokio::fs::File`.
+    ///
+    /// The inner file will be deleted.
+    pub fn into_file(self) -> tokio::fs::File {
+        self.file
+    }
+
+    /// Closes the file, leaving only the temporary file path.
+    ///
+    /// The returned [`TempPath`] removes the file synchronously when dropped.
+    pub fn into_temp_path(self) -> TempPath {
+        self.path.into_inner()
+    }
+
+    /// Converts the temporary file into its parts.
+    pub fn into_parts(self) -> (tokio::fs::File, TempPath) {
+        (self.file, self.path.into_inner())
+    }
+
+    /// Creates an `AsyncNamedTempFile` from its parts.
+    pub fn from_parts(file: tokio::fs::File, path: TempPath) -> Self {
+        AsyncNamedTempFile {
+            path: BackgroundPath(path),
+            file,
+        }
+    }
+}
+
+impl AsyncRead for AsyncNamedTempFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        Pin::new(&mut this.file)
+            .poll_read(cx, buf)
+            .map(|res| res.with_err_path(|| this.path()))
+    }
+}
+
+impl AsyncWrite for AsyncNamedTempFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        Pin::new(&mut this.file)
+            .poll_write(cx, buf)
+            .map(|res| res.with_err_path(|| this.path()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        Pin::new(&mut this.file)
+            .poll_flush(cx)
+            .map(|res| res.with_err_path(|| this.path()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        Pin::new(&mut this.file)
+            .poll_shutdown(cx)
+            .map(|res| res.with_err_path(|| this.path()))
+    }
+}
+
+impl AsyncSeek for AsyncNamedTempFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        let this = &mut *self;
+        Pin::new(&mut this.file)
+            .start_seek(position)
+            .with_err_path(|| this.path())
+    }
+
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let this = &mut *self;
+        Pin::new(&mut this.file)
+            .poll_complete(cx)
+            .map(|res| res.with_err_path(|| this.path()))
+    }
+}
+
+impl<'a, 'b> Builder<'a, 'b> {
+    /// Create the named temporary file without blocking the async runtime.
+    ///
+    /// This behaves like [`Builder::tempfile`], but returns an
+    /// [`AsyncNamedTempFile`]. Requires the `tokio` feature.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be created, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let file = Builder::new()
+    ///     .prefix("my-temporary-note")
+    ///     .tempfile_async(|dir, name| Path::new(dir).join(name))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn tempfile_async<J>(&self, join: J) -> io::Result<AsyncNamedTempFile>
+    where
+        J: Fn(&OsStr, &OsStr) -> PathBuf + Send + 'static,
+    {
+        self.tempfile_in_async(crate::env::temp_dir(), join).await
+    }
+
+    /// Create the named temporary file in the specified directory without
+    /// blocking the async runtime.
+    ///
+    /// This behaves like [`Builder::tempfile_in`], but returns an
+    /// [`AsyncNamedTempFile`]. Requires the `tokio` feature.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be created, `Err` is returned.
+    pub async fn tempfile_in_async<P, J>(&self, dir: P, join: J) -> io::Result<AsyncNamedTempFile>
+    where
+        P: AsRef<Path>,
+        J: Fn(&OsStr, &OsStr) -> PathBuf + Send + 'static,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        let owned = OwnedBuilder::new(self);
+        unblock(move || owned.builder().tempfile_in(dir, join))
+            .await
+            .map(AsyncNamedTempFile::from)
+    }
+}
+
+/// A copy of a `Builder` that can be moved to the blocking thread pool.
+struct OwnedBuilder {
+    prefix: OsString,
+    suffix: OsString,
+    template: Builder<'static, 'static>,
+}
+
+impl OwnedBuilder {
+    fn new(builder: &Builder<'_, '_>) -> Self {
+        OwnedBuilder {
+            prefix: builder.prefix.to_owned(),
+            suffix: builder.suffix.to_owned(),
+            template: Builder {
+                random_len: builder.random_len,
+                prefix: OsStr::new(""),
+                suffix: OsStr::new(""),
+                append: builder.append,
+                permissions: builder.permissions.clone(),
+                keep: builder.keep,
+                name_generator: builder.name_generator.clone(),
+                num_retries: builder.num_retries,
+                lock: builder.lock,
+                memory_backed: builder.memory_backed,
+                allow_sealing: builder.allow_sealing,
+            },
+        }
+    }
+
+    fn builder(&self) -> Builder<'_, '_> {
+        let mut builder: Builder<'_, '_> = self.template.clone();
+        builder.prefix(&self.prefix).suffix(&self.suffix);
+        builder
+    }
+}
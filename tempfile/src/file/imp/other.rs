@@ -1,6 +1,7 @@
+use std::ffi::OsStr;
 use std::fs::{File, OpenOptions};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 fn not_supported<T>() -> io::Result<T> {
     Err(io::Error::new(
@@ -17,7 +18,7 @@ pub fn create_named(
     not_supported()
 }
 
-pub fn create(_dir: &Path) -> io::Result<File> {
+pub fn create<J: Fn(&OsStr, &OsStr) -> PathBuf>(_dir: &Path, _join: J) -> io::Result<File> {
     not_supported()
 }
 
@@ -32,3 +33,15 @@ pub fn persist(_old_path: &Path, _new_path: &Path, _overwrite: bool) -> io::Resu
 pub fn keep(_path: &Path) -> io::Result<()> {
     not_supported()
 }
+
+pub fn lock(_file: &File, _path: &Path) -> io::Result<()> {
+    not_supported()
+}
+
+pub fn reap(_path: &Path) -> io::Result<bool> {
+    not_supported()
+}
+
+pub fn create_memfd(_name: &std::ffi::OsStr, _allow_sealing: bool) -> io::Result<File> {
+    not_supported()
+}
@@ -9,7 +9,7 @@ cfg_if::cfg_if! {
         use std::os::wasi::fs::MetadataExt;
     }
 }
-use crate::util;
+use crate::{util, Builder};
 use std::path::{Path, PathBuf};
 
 #[cfg(not(target_os = "redox"))]
@@ -52,7 +52,7 @@ fn create_unlinked<J: Fn(&OsStr, &OsStr) -> PathBuf>(path: &Path, join: J) -> io
 }
 
 #[cfg(target_os = "linux")]
-pub fn create(dir: &Path) -> io::Result<File> {
+pub fn create<J: Fn(&OsStr, &OsStr) -> PathBuf>(dir: &Path, join: J) -> io::Result<File> {
     use rustix::{fs::OFlags, io::Errno};
     use std::os::unix::fs::OpenOptionsExt;
     OpenOptions::new()
@@ -64,7 +64,7 @@ pub fn create(dir: &Path) -> io::Result<File> {
             match Errno::from_io_error(&e) {
                 // These are the three "not supported" error codes for O_TMPFILE.
                 Some(Errno::OPNOTSUPP) | Some(Errno::ISDIR) | Some(Errno::NOENT) => {
-                    create_unix(dir)
+                    create_unix(dir, &join)
                 }
                 _ => Err(e),
             }
@@ -79,9 +79,7 @@ pub fn create<J: Fn(&OsStr, &OsStr) -> PathBuf>(dir: &Path, join: J) -> io::Resu
 fn create_unix<J: Fn(&OsStr, &OsStr) -> PathBuf>(dir: &Path, join: J) -> io::Result<File> {
     util::create_helper(
         dir,
-        OsStr::new(".tmp"),
-        OsStr::new(""),
-        crate::NUM_RAND_CHARS,
+        &Builder::new(),
         |path| create_unlinked(&path, &join),
         &join
     )
@@ -154,3 +152,111 @@ pub fn persist(_old_path: &Path, _new_path: &Path, _overwrite: bool) -> io::Resu
 pub fn keep(_: &Path) -> io::Result<()> {
     Ok(())
 }
+
+#[cfg(not(any(target_os = "espidf", target_os = "vita", target_os = "wasi")))]
+fn try_lock(file: &File) -> io::Result<()> {
+    use rustix::fs::FlockOperation;
+    #[cfg(not(target_os = "solaris"))]
+    let res = rustix::fs::flock(file, FlockOperation::NonBlockingLockExclusive);
+    // Solaris has no `flock`, fall back to a whole-file `fcntl` lock.
+    #[cfg(target_os = "solaris")]
+    let res = rustix::fs::fcntl_lock(file, FlockOperation::NonBlockingLockExclusive);
+    res.map_err(io::Error::from)
+}
+
+#[cfg(not(any(target_os = "espidf", target_os = "vita", target_os = "wasi")))]
+pub fn lock(file: &File, path: &Path) -> io::Result<()> {
+    try_lock(file).map_err(|e| {
+        if e.kind() == io::ErrorKind::WouldBlock {
+            // Someone else (most likely `reap_stale`) grabbed the file before
+            // we could lock it. Report it as taken so that another name is
+            // tried.
+            io::Error::new(io::ErrorKind::AlreadyExists, e)
+        } else {
+            e
+        }
+    })?;
+
+    // `reap_stale` may also have locked and removed the file, and released it,
+    // before we got the lock. Only keep the file if the name still refers to it.
+    let meta = file.metadata()?;
+    match fs::symlink_metadata(path) {
+        Ok(now) if now.dev() == meta.dev() && now.ino() == meta.ino() => Ok(()),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "temporary file was removed before it could be locked",
+        )),
+    }
+}
+
+#[cfg(any(target_os = "espidf", target_os = "vita", target_os = "wasi"))]
+pub fn lock(_file: &File, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "file locking is not supported on this platform",
+    ))
+}
+
+#[cfg(not(any(target_os = "espidf", target_os = "vita", target_os = "wasi")))]
+pub fn reap(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = match OpenOptions::new()
+        .read(true)
+        .custom_flags((rustix::fs::OFlags::NOFOLLOW | rustix::fs::OFlags::NONBLOCK).bits() as i32)
+        .open(path)
+    {
+        Ok(file) => file,
+        // Gone already, or not something we created (e.g. a symlink).
+        Err(_) => return Ok(false),
+    };
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        return Ok(false);
+    }
+    match try_lock(&file) {
+        Ok(()) => {}
+        // Still held by a live temporary file.
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+        Err(e) => return Err(e),
+    }
+
+    // Make sure that the name still refers to the file we locked before
+    // removing it.
+    match fs::symlink_metadata(path) {
+        Ok(now) if now.dev() == meta.dev() && now.ino() == meta.ino() => {}
+        _ => return Ok(false),
+    }
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(any(target_os = "espidf", target_os = "vita", target_os = "wasi"))]
+pub fn reap(_path: &Path) -> io::Result<bool> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "file locking is not supported on this platform",
+    ))
+}
+
+#[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+pub fn create_memfd(name: &OsStr, allow_sealing: bool) -> io::Result<File> {
+    use rustix::fs::{memfd_create, MemfdFlags};
+    let mut flags = MemfdFlags::CLOEXEC;
+    if allow_sealing {
+        flags |= MemfdFlags::ALLOW_SEALING;
+    }
+    Ok(memfd_create(name, flags)?.into())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "freebsd", target_os = "linux")))]
+pub fn create_memfd(_name: &OsStr, _allow_sealing: bool) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "memory-backed files are not supported on this platform",
+    ))
+}
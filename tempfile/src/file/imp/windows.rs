@@ -3,7 +3,7 @@ use std::fs::{File, OpenOptions};
 use std::os::windows::ffi::OsStrExt;
 use std::os::windows::fs::OpenOptionsExt;
 use std::os::windows::io::{AsRawHandle, FromRawHandle, RawHandle};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{io, iter};
 
 use windows_sys::Win32::Foundation::{HANDLE, INVALID_HANDLE_VALUE};
@@ -13,7 +13,7 @@ use windows_sys::Win32::Storage::FileSystem::{
     FILE_SHARE_READ, FILE_SHARE_WRITE, MOVEFILE_REPLACE_EXISTING,
 };
 
-use crate::util;
+use crate::{util, Builder};
 
 fn to_utf16(s: &Path) -> Vec<u16> {
     s.as_os_str().encode_wide().chain(iter::once(0)).collect()
@@ -39,12 +39,10 @@ pub fn create_named(
         .open(path)
 }
 
-pub fn create(dir: &Path) -> io::Result<File> {
+pub fn create<J: Fn(&OsStr, &OsStr) -> PathBuf>(dir: &Path, join: J) -> io::Result<File> {
     util::create_helper(
         dir,
-        OsStr::new(".tmp"),
-        OsStr::new(""),
-        crate::NUM_RAND_CHARS,
+        &Builder::new(),
         |path| {
             OpenOptions::new()
                 .create_new(true)
@@ -54,6 +52,7 @@ pub fn create(dir: &Path) -> io::Result<File> {
                 .custom_flags(FILE_ATTRIBUTE_TEMPORARY | FILE_FLAG_DELETE_ON_CLOSE)
                 .open(path)
         },
+        join,
     )
 }
 
@@ -113,3 +112,15 @@ pub fn persist(old_path: &Path, new_path: &Path, overwrite: bool) -> io::Result<
         }
     }
 }
+
+pub fn lock(_file: &File, _path: &Path) -> io::Result<()> {
+    not_supported("file locking is not supported on this platform")
+}
+
+pub fn reap(_path: &Path) -> io::Result<bool> {
+    not_supported("file locking is not supported on this platform")
+}
+
+pub fn create_memfd(_name: &std::ffi::OsStr, _allow_sealing: bool) -> io::Result<File> {
+    not_supported("memory-backed files are not supported on this platform")
+}
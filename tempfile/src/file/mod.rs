@@ -15,10 +15,16 @@ use std::path::{Path, PathBuf};
 
 use crate::env;
 use crate::error::IoResultExt;
+use crate::util;
 use crate::Builder;
 
 mod imp;
 
+#[cfg(feature = "tokio")]
+mod async_file;
+#[cfg(feature = "tokio")]
+pub use self::async_file::{AsyncNamedTempFile, AsyncPersistError};
+
 /// Create a new temporary file.
 ///
 /// The file will be created in the location returned by [`env::temp_dir()`].
@@ -84,6 +90,135 @@ pub fn tempfile_in<P: AsRef<Path>, J: Fn(&OsStr, &OsStr) -> PathBuf>(dir: P, joi
     imp::create(dir.as_ref(), join)
 }
 
+/// Create a new anonymous temporary file backed by memory.
+///
+/// This doesn't need a writable filesystem, which makes it suitable for
+/// sandboxed processes. The file behaves like a regular file, but its contents
+/// live in memory (and swap) and are released when the last handle to it is
+/// closed. Use [`Builder::memory_backed`] to also allow sealing the file.
+///
+/// # Platform Notes
+///
+/// This uses `memfd_create` and is only supported on Linux, Android and
+/// FreeBSD. On other platforms, `Err` is always returned.
+///
+/// # Errors
+///
+/// If the file can not be created, `Err` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+///
+/// let mut file = tempfile::memfd()?;
+/// writeln!(file, "Brian was here. Briefly.")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn memfd() -> io::Result<File> {
+    imp::create_memfd(OsStr::new(".tmp"), false)
+}
+
+/// Remove temporary files left behind in `dir` by processes that no longer
+/// exist.
+///
+/// Only regular files that match the default naming scheme of a [`Builder`]
+/// with the given `prefix` and that aren't locked are removed: the name must
+/// consist of `prefix` followed by exactly six random characters, with no
+/// suffix. Temporary files hold such a lock for their whole lifetime when
+/// created by a [`Builder`] with [`Builder::lock`] enabled. Files created
+/// without locking look abandoned and will be removed as well, so only use
+/// this on directories where every temporary file with the given prefix is
+/// locked. Use [`Builder::reap_stale_in`] to match another naming scheme, or
+/// [`reap_stale_by_prefix`] to match on the prefix alone.
+///
+/// Returns the paths of the removed files.
+///
+/// # Errors
+///
+/// If `dir` cannot be read or a stale file cannot be removed, `Err` is
+/// returned. On platforms without advisory file locks, this always fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// let removed = tempfile::reap_stale("/var/tmp/my-service", ".tmp", |dir, name| {
+///     Path::new(dir).join(name)
+/// })?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn reap_stale<P: AsRef<Path>, S: AsRef<OsStr>, J: Fn(&OsStr, &OsStr) -> PathBuf>(
+    dir: P,
+    prefix: S,
+    join: J,
+) -> io::Result<Vec<PathBuf>> {
+    let prefix = prefix.as_ref();
+    reap_matching(
+        dir.as_ref(),
+        |name| util::matches_name(name, prefix, OsStr::new(""), Some(crate::NUM_RAND_CHARS)),
+        join,
+    )
+}
+
+/// Remove all unlocked regular files in `dir` whose names start with
+/// `prefix`.
+///
+/// This is like [`reap_stale`], but ignores the rest of the name. Other
+/// applications may use the same prefix, `.tmp` in particular, so only use
+/// this on directories that are private to your application.
+///
+/// Returns the paths of the removed files.
+///
+/// # Errors
+///
+/// If `dir` cannot be read or a stale file cannot be removed, `Err` is
+/// returned. On platforms without advisory file locks, this always fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// let removed = tempfile::reap_stale_by_prefix("/var/tmp/my-service", "job-", |dir, name| {
+///     Path::new(dir).join(name)
+/// })?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn reap_stale_by_prefix<P: AsRef<Path>, S: AsRef<OsStr>, J: Fn(&OsStr, &OsStr) -> PathBuf>(
+    dir: P,
+    prefix: S,
+    join: J,
+) -> io::Result<Vec<PathBuf>> {
+    let prefix = prefix.as_ref();
+    reap_matching(
+        dir.as_ref(),
+        |name| util::matches_name(name, prefix, OsStr::new(""), None),
+        join,
+    )
+}
+
+pub(crate) fn reap_matching<J: Fn(&OsStr, &OsStr) -> PathBuf>(
+    dir: &Path,
+    matches: impl Fn(&OsStr) -> bool,
+    join: J,
+) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for entry in fs::read_dir(dir).with_err_path(|| dir)? {
+        let entry = entry.with_err_path(|| dir)?;
+        let name = entry.file_name();
+        if !matches(&name) {
+            continue;
+        }
+        let path = join(dir.as_os_str(), &name);
+        if imp::reap(&path).with_err_path(|| &path)? {
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
 /// Error returned when persisting a temporary file path fails.
 #[derive(Debug)]
 pub struct PathPersistError {
@@ -1044,11 +1179,16 @@ impl<F: AsRawHandle> AsRawHandle for NamedTempFile<F> {
     }
 }
 
+pub(crate) fn create_memfd(name: &OsStr, allow_sealing: bool) -> io::Result<File> {
+    imp::create_memfd(name, allow_sealing)
+}
+
 pub(crate) fn create_named<J: Fn(&OsStr, &OsStr) -> PathBuf>(
     mut path: PathBuf,
     open_options: &mut OpenOptions,
     permissions: Option<&std::fs::Permissions>,
     keep: bool,
+    lock: bool,
     join: J
 ) -> io::Result<NamedTempFile> {
     // Make the path absolute. Otherwise, changing directories could cause us to
@@ -1057,6 +1197,20 @@ pub(crate) fn create_named<J: Fn(&OsStr, &OsStr) -> PathBuf>(
         path = join(std::env::current_dir()?.as_ref(), path.as_ref());
     }
     imp::create_named(&path, open_options, permissions)
+        .and_then(|file| {
+            if lock {
+                if let Err(e) = imp::lock(&file, &path) {
+                    // The name is only reported as taken when the file was
+                    // (or is being) removed by `reap_stale`, in which case it
+                    // may already refer to someone else's file.
+                    if e.kind() != io::ErrorKind::AlreadyExists {
+                        let _ = fs::remove_file(&path);
+                    }
+                    return Err(e);
+                }
+            }
+            Ok(file)
+        })
         .with_err_path(|| path.clone())
         .map(|file| NamedTempFile {
             path: TempPath {
@@ -0,0 +1,561 @@
+//! Declarative directory trees for tests.
+//!
+//! A [`Tree`] describes files, directories and symlinks relative to a root.
+//! It can be materialized into a fresh [`TempDir`], read back from disk, and
+//! compared against another tree, producing a readable list of
+//! [`Difference`]s.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::Path;
+//! use tempfile::fixture::{self, Tree};
+//!
+//! let join = |dir: &_, name: &_| Path::new(dir).join(name);
+//! let tree = Tree::new()
+//!     .file("src/lib.rs", "pub fn answer() -> u32 { 42 }")
+//!     .dir("target")
+//!     .symlink("latest", "target");
+//!
+//! let dir = tree.create(join)?;
+//!
+//! std::fs::write(dir.path().join("target/out"), "done")?;
+//!
+//! fixture::assert_tree(dir.path(), &tree.clone().file("target/out", "done"), join);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::IoResultExt;
+use crate::{Builder, TempDir};
+
+/// The kind of an entry in a [`Tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::File => "file",
+            Kind::Dir => "directory",
+            Kind::Symlink => "symlink",
+        })
+    }
+}
+
+/// A single entry in a [`Tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    /// A regular file with the given contents.
+    File {
+        contents: Vec<u8>,
+        permissions: Option<fs::Permissions>,
+    },
+    /// A directory.
+    Dir { permissions: Option<fs::Permissions> },
+    /// A symbolic link pointing at `target`.
+    Symlink { target: PathBuf },
+}
+
+impl Entry {
+    /// Returns the kind of this entry.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Entry::File { .. } => Kind::File,
+            Entry::Dir { .. } => Kind::Dir,
+            Entry::Symlink { .. } => Kind::Symlink,
+        }
+    }
+
+    fn permissions(&self) -> Option<&fs::Permissions> {
+        match self {
+            Entry::File { permissions, .. } | Entry::Dir { permissions } => permissions.as_ref(),
+            Entry::Symlink { .. } => None,
+        }
+    }
+}
+
+/// A declarative description of a directory tree.
+///
+/// Paths are relative to the root of the tree. Parent directories are added
+/// implicitly. Paths that are absolute, contain `.` or `..` components or
+/// pass through a file or symlink of the tree are rejected, as are files and
+/// symlinks that would replace a directory with children. Writing a tree that
+/// contains a rejected path fails.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tree {
+    entries: BTreeMap<PathBuf, Entry>,
+    invalid: Option<PathBuf>,
+}
+
+impl Tree {
+    /// Create an empty tree.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file with the given contents.
+    #[must_use]
+    pub fn file<P: AsRef<Path>, C: AsRef<[u8]>>(self, path: P, contents: C) -> Self {
+        self.insert(
+            path.as_ref(),
+            Entry::File {
+                contents: contents.as_ref().to_vec(),
+                permissions: None,
+            },
+        )
+    }
+
+    /// Add an empty directory.
+    #[must_use]
+    pub fn dir<P: AsRef<Path>>(self, path: P) -> Self {
+        self.insert(path.as_ref(), Entry::Dir { permissions: None })
+    }
+
+    /// Add a symlink pointing at `target`.
+    ///
+    /// The target is stored verbatim and is not required to exist.
+    #[must_use]
+    pub fn symlink<P: AsRef<Path>, T: AsRef<Path>>(self, path: P, target: T) -> Self {
+        self.insert(
+            path.as_ref(),
+            Entry::Symlink {
+                target: target.as_ref().to_path_buf(),
+            },
+        )
+    }
+
+    /// Set the permissions of a previously added file or directory.
+    ///
+    /// Permissions are applied after the whole tree has been written, so a
+    /// read-only directory may still be populated. When comparing trees,
+    /// permissions are only checked for entries that specify them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` is not a file or directory of this tree.
+    #[must_use]
+    pub fn permissions<P: AsRef<Path>>(mut self, path: P, perms: fs::Permissions) -> Self {
+        let path = path.as_ref();
+        match self.entries.get_mut(path) {
+            Some(Entry::File { permissions, .. }) | Some(Entry::Dir { permissions }) => {
+                *permissions = Some(perms);
+            }
+            _ => panic!("no file or directory at {:?} in the fixture tree", path),
+        }
+        self
+    }
+
+    /// Returns the entry at `path`, if any.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&Entry> {
+        self.entries.get(path.as_ref())
+    }
+
+    /// Returns an iterator over all entries, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Entry)> {
+        self.entries.iter().map(|(p, e)| (p.as_path(), e))
+    }
+
+    fn insert(mut self, path: &Path, entry: Entry) -> Self {
+        let is_normal = path.components().all(|c| matches!(c, Component::Normal(_)));
+        if !is_normal || path.as_os_str().is_empty() {
+            self.invalid.get_or_insert_with(|| path.to_path_buf());
+            return self;
+        }
+        // A file or symlink cannot have children, since writing them would go
+        // through a symlink or fail.
+        let is_parent = |p: &Path| self.entries.get(p).map_or(true, |e| e.kind() == Kind::Dir);
+        let has_children = || self.entries.keys().any(|p| p != path && p.starts_with(path));
+        let parents = path.ancestors().skip(1).take_while(|p| !p.as_os_str().is_empty());
+        if !parents.clone().all(is_parent) || (entry.kind() != Kind::Dir && has_children()) {
+            self.invalid.get_or_insert_with(|| path.to_path_buf());
+            return self;
+        }
+        for parent in parents {
+            self.entries
+                .entry(parent.to_path_buf())
+                .or_insert(Entry::Dir { permissions: None });
+        }
+        self.entries.insert(path.to_path_buf(), entry);
+        self
+    }
+
+    /// Create a new temporary directory and write this tree into it.
+    ///
+    /// # Errors
+    ///
+    /// If the directory or any of the entries cannot be created, `Err` is
+    /// returned.
+    pub fn create<J: Fn(&OsStr, &OsStr) -> PathBuf>(&self, join: J) -> io::Result<TempDir> {
+        let dir = Builder::new().tempdir(&join)?;
+        self.write_to(dir.path(), join)?;
+        Ok(dir)
+    }
+
+    /// Create a new temporary directory inside of `dir` and write this tree
+    /// into it.
+    ///
+    /// # Errors
+    ///
+    /// If the directory or any of the entries cannot be created, `Err` is
+    /// returned.
+    pub fn create_in<P: AsRef<Path>, J: Fn(&OsStr, &OsStr) -> PathBuf>(
+        &self,
+        dir: P,
+        join: J,
+    ) -> io::Result<TempDir> {
+        let dir = Builder::new().tempdir_in(dir, &join)?;
+        self.write_to(dir.path(), join)?;
+        Ok(dir)
+    }
+
+    /// Write this tree into the existing directory `root`.
+    ///
+    /// # Errors
+    ///
+    /// If a path of the tree was rejected, an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned without writing anything.
+    /// If any of the entries cannot be created, `Err` is returned.
+    pub fn write_to<J: Fn(&OsStr, &OsStr) -> PathBuf>(&self, root: &Path, join: J) -> io::Result<()> {
+        if let Some(invalid) = &self.invalid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fixture paths must be relative, must not contain `.` or `..` \
+                 and must not pass through a file or symlink",
+            ))
+            .with_err_path(|| invalid);
+        }
+        for (rel, entry) in &self.entries {
+            let path = join(root.as_os_str(), rel.as_os_str());
+            match entry {
+                Entry::File { contents, .. } => fs::write(&path, contents),
+                Entry::Dir { .. } => fs::create_dir(&path),
+                Entry::Symlink { target } => {
+                    symlink(target, &path, self.links_to_dir(root, rel, target, &join))
+                }
+            }
+            .with_err_path(|| &path)?;
+        }
+
+        // Children sort after their parents, so applying permissions in
+        // reverse order never locks us out of a directory we still need.
+        for (rel, entry) in self.entries.iter().rev() {
+            if let Some(perms) = entry.permissions() {
+                let path = join(root.as_os_str(), rel.as_os_str());
+                fs::set_permissions(&path, perms.clone()).with_err_path(|| &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the symlink at `rel` points at a directory of this tree,
+    /// or at an existing directory outside of it.
+    fn links_to_dir<J: Fn(&OsStr, &OsStr) -> PathBuf>(
+        &self,
+        root: &Path,
+        rel: &Path,
+        target: &Path,
+        join: J,
+    ) -> bool {
+        let parent = rel.parent().unwrap_or_else(|| Path::new(""));
+        let mut resolved = parent.to_path_buf();
+        for component in target.components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                Component::ParentDir if resolved.pop() => {}
+                _ => {
+                    resolved.clear();
+                    break;
+                }
+            }
+        }
+        if let Some(Entry::Dir { .. }) = self.entries.get(&resolved) {
+            return true;
+        }
+        let parent = join(root.as_os_str(), parent.as_os_str());
+        join(parent.as_os_str(), target.as_os_str()).is_dir()
+    }
+
+    /// Read the tree rooted at `root` from disk.
+    ///
+    /// Symlinks are not followed. Permissions are recorded for every file and
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// If `root` or any directory beneath it cannot be read, `Err` is
+    /// returned.
+    pub fn read<J: Fn(&OsStr, &OsStr) -> PathBuf>(root: &Path, join: J) -> io::Result<Tree> {
+        let mut tree = Tree::new();
+        let mut pending = vec![PathBuf::new()];
+        while let Some(rel) = pending.pop() {
+            let dir = join(root.as_os_str(), rel.as_os_str());
+            for dent in fs::read_dir(&dir).with_err_path(|| &dir)? {
+                let dent = dent.with_err_path(|| &dir)?;
+                let child = join(rel.as_os_str(), &dent.file_name());
+                let path = join(dir.as_os_str(), &dent.file_name());
+                let meta = fs::symlink_metadata(&path).with_err_path(|| &path)?;
+                let entry = if meta.file_type().is_symlink() {
+                    Entry::Symlink {
+                        target: fs::read_link(&path).with_err_path(|| &path)?,
+                    }
+                } else if meta.is_dir() {
+                    pending.push(child.clone());
+                    Entry::Dir {
+                        permissions: Some(meta.permissions()),
+                    }
+                } else {
+                    Entry::File {
+                        contents: fs::read(&path).with_err_path(|| &path)?,
+                        permissions: Some(meta.permissions()),
+                    }
+                };
+                tree.entries.insert(child, entry);
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Compare `actual` against this tree, treating `self` as the expected
+    /// state.
+    ///
+    /// Returns an empty list if the trees match.
+    pub fn diff(&self, actual: &Tree) -> Vec<Difference> {
+        let mut diffs = Vec::new();
+        for (path, expected) in &self.entries {
+            let actual = match actual.entries.get(path) {
+                Some(actual) => actual,
+                None => {
+                    diffs.push(Difference::Missing {
+                        path: path.clone(),
+                        kind: expected.kind(),
+                    });
+                    continue;
+                }
+            };
+            match (expected, actual) {
+                (
+                    Entry::File { contents: e, .. },
+                    Entry::File { contents: a, .. },
+                ) if e != a => diffs.push(Difference::Contents {
+                    path: path.clone(),
+                    expected: e.clone(),
+                    actual: a.clone(),
+                }),
+                (Entry::Symlink { target: e }, Entry::Symlink { target: a }) if e != a => {
+                    diffs.push(Difference::Target {
+                        path: path.clone(),
+                        expected: e.clone(),
+                        actual: a.clone(),
+                    })
+                }
+                _ if expected.kind() != actual.kind() => diffs.push(Difference::Kind {
+                    path: path.clone(),
+                    expected: expected.kind(),
+                    actual: actual.kind(),
+                }),
+                _ => {}
+            }
+            if let (Some(e), Some(a)) = (expected.permissions(), actual.permissions()) {
+                if !same_permissions(e, a) {
+                    diffs.push(Difference::Permissions {
+                        path: path.clone(),
+                        expected: e.clone(),
+                        actual: a.clone(),
+                    });
+                }
+            }
+        }
+        for (path, entry) in &actual.entries {
+            if !self.entries.contains_key(path) {
+                diffs.push(Difference::Unexpected {
+                    path: path.clone(),
+                    kind: entry.kind(),
+                });
+            }
+        }
+        diffs
+    }
+}
+
+/// A single mismatch between an expected and an actual [`Tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// An expected entry does not exist.
+    Missing { path: PathBuf, kind: Kind },
+    /// An entry exists that was not expected.
+    Unexpected { path: PathBuf, kind: Kind },
+    /// An entry exists but has a different kind.
+    Kind {
+        path: PathBuf,
+        expected: Kind,
+        actual: Kind,
+    },
+    /// A file has different contents.
+    Contents {
+        path: PathBuf,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// A symlink points somewhere else.
+    Target {
+        path: PathBuf,
+        expected: PathBuf,
+        actual: PathBuf,
+    },
+    /// A file or directory has different permissions.
+    Permissions {
+        path: PathBuf,
+        expected: fs::Permissions,
+        actual: fs::Permissions,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Missing { path, kind } => write!(f, "- {} ({})", path.display(), kind),
+            Difference::Unexpected { path, kind } => write!(f, "+ {} ({})", path.display(), kind),
+            Difference::Kind {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "~ {}: expected a {}, found a {}",
+                path.display(),
+                expected,
+                actual
+            ),
+            Difference::Contents {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "~ {}: contents differ\n    expected: {}\n    actual:   {}",
+                path.display(),
+                Contents(expected),
+                Contents(actual)
+            ),
+            Difference::Target {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "~ {}: expected a link to {:?}, found a link to {:?}",
+                path.display(),
+                expected,
+                actual
+            ),
+            Difference::Permissions {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "~ {}: expected permissions {}, found {}",
+                path.display(),
+                Mode(expected),
+                Mode(actual)
+            ),
+        }
+    }
+}
+
+/// Assert that the directory at `root` matches `expected`.
+///
+/// # Panics
+///
+/// Panics with a line per [`Difference`] if the trees don't match, or if
+/// `root` cannot be read.
+#[track_caller]
+pub fn assert_tree<J: Fn(&OsStr, &OsStr) -> PathBuf>(root: &Path, expected: &Tree, join: J) {
+    let actual = match Tree::read(root, join) {
+        Ok(actual) => actual,
+        Err(e) => panic!("failed to read {:?}: {}", root, e),
+    };
+    let diffs = expected.diff(&actual);
+    if !diffs.is_empty() {
+        let mut msg = format!("directory tree at {:?} does not match:", root);
+        for diff in diffs {
+            msg.push('\n');
+            msg.push_str(&diff.to_string());
+        }
+        panic!("{}", msg);
+    }
+}
+
+/// Formats file contents as text when possible, and as a byte count otherwise.
+struct Contents<'a>(&'a [u8]);
+
+impl fmt::Display for Contents<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(self.0) {
+            Ok(s) => write!(f, "{:?}", s),
+            Err(_) => write!(f, "<{} bytes of binary data>", self.0.len()),
+        }
+    }
+}
+
+struct Mode<'a>(&'a fs::Permissions);
+
+impl fmt::Display for Mode<'_> {
+    #[cfg(unix)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::os::unix::fs::PermissionsExt;
+        write!(f, "{:o}", self.0.mode() & 0o7777)
+    }
+
+    #[cfg(not(unix))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.readonly() { "read-only" } else { "writable" })
+    }
+}
+
+#[cfg(unix)]
+fn same_permissions(a: &fs::Permissions, b: &fs::Permissions) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    // Permissions read from disk include the file type bits.
+    a.mode() & 0o7777 == b.mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn same_permissions(a: &fs::Permissions, b: &fs::Permissions) -> bool {
+    a.readonly() == b.readonly()
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path, _is_dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path, is_dir: bool) -> io::Result<()> {
+    if is_dir {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::os::windows::fs::symlink_file(target, path)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _path: &Path, _is_dir: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "symlinks are not supported on this platform",
+    ))
+}
@@ -148,12 +148,14 @@
 doc_comment::doctest!("../README.md");
 
 const NUM_RETRIES: u32 = 1 << 31;
+const NUM_CUSTOM_RETRIES: u32 = 16;
 const NUM_RAND_CHARS: usize = 6;
 
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs::OpenOptions;
 use std::io;
 use std::path::{PathBuf, Path};
+use std::sync::{Arc, Mutex};
 
 mod dir;
 mod error;
@@ -162,11 +164,15 @@ mod spooled;
 mod util;
 
 pub mod env;
+pub mod fixture;
 
 pub use crate::dir::{tempdir, tempdir_in, TempDir};
 pub use crate::file::{
-    tempfile, tempfile_in, NamedTempFile, PathPersistError, PersistError, TempPath,
+    memfd, reap_stale, reap_stale_by_prefix, tempfile, tempfile_in, NamedTempFile,
+    PathPersistError, PersistError, TempPath,
 };
+#[cfg(feature = "tokio")]
+pub use crate::file::{AsyncNamedTempFile, AsyncPersistError};
 pub use crate::spooled::{spooled_tempfile, SpooledData, SpooledTempFile};
 
 /// Create a new temporary file or directory with custom parameters.
@@ -178,6 +184,11 @@ pub struct Builder<'a, 'b> {
     append: bool,
     permissions: Option<std::fs::Permissions>,
     keep: bool,
+    name_generator: util::NameGenerator,
+    num_retries: Option<u32>,
+    lock: bool,
+    memory_backed: bool,
+    allow_sealing: bool,
 }
 
 impl<'a, 'b> Default for Builder<'a, 'b> {
@@ -189,6 +200,11 @@ impl<'a, 'b> Default for Builder<'a, 'b> {
             append: false,
             permissions: None,
             keep: false,
+            name_generator: util::NameGenerator::Random,
+            num_retries: None,
+            lock: false,
+            memory_backed: false,
+            allow_sealing: false,
         }
     }
 }
@@ -435,6 +451,317 @@ impl<'a, 'b> Builder<'a, 'b> {
         self
     }
 
+    /// Use a custom generator for the variable part of temporary file names.
+    ///
+    /// The generator is called once per attempt with the zero-based attempt
+    /// number, and its output is placed between the [prefix](Self::prefix) and
+    /// the [suffix](Self::suffix). If the resulting name already exists, the
+    /// generator is called again, up to the number of [retries](Self::retries),
+    /// which defaults to 16 for custom generators.
+    /// [`Builder::rand_bytes`] is ignored when a custom generator is set.
+    ///
+    /// This can be used to draw names from a cryptographically secure random
+    /// number generator, or to produce predictable names in tests.
+    ///
+    /// Cloning the builder shares the generator between the clones.
+    ///
+    /// # Security
+    ///
+    /// The default generator makes names hard to guess, which matters when the
+    /// temporary directory is shared with other users. A generator producing
+    /// predictable names makes the temporary file easier to target, see
+    /// [the security][security] docs on `NamedTempFile`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ffi::OsString;
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let named_tempfile = Builder::new()
+    ///     .prefix("my-temporary-note")
+    ///     .name_generator(|attempt| OsString::from(format!("-{}", attempt)))
+    ///     .tempfile(|dir, name| Path::new(dir).join(name))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// [security]: struct.NamedTempFile.html#security
+    pub fn name_generator<G>(&mut self, generator: G) -> &mut Self
+    where
+        G: FnMut(u32) -> OsString + Send + 'static,
+    {
+        self.name_generator = util::NameGenerator::Custom(Arc::new(Mutex::new(generator)));
+        self
+    }
+
+    /// Generate the random part of temporary file names from a fixed seed.
+    ///
+    /// Builders seeded with the same value produce the same sequence of names,
+    /// which makes temporary paths reproducible across runs (e.g. in snapshot
+    /// tests). The sequence continues across calls on the same builder and its
+    /// clones.
+    ///
+    /// # Security
+    ///
+    /// Seeded names are predictable. Don't use this in a directory shared with
+    /// untrusted users, see [the security][security] docs on `NamedTempFile`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let join = |dir: &_, name: &_| Path::new(dir).join(name);
+    /// let dir = tempfile::tempdir(join)?;
+    /// let a = Builder::new().rand_seed(42).tempfile_in(dir.path(), join)?;
+    ///
+    /// let other = tempfile::tempdir(join)?;
+    /// let b = Builder::new().rand_seed(42).tempfile_in(other.path(), join)?;
+    ///
+    /// assert_eq!(a.path().file_name(), b.path().file_name());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// [security]: struct.NamedTempFile.html#security
+    pub fn rand_seed(&mut self, seed: u64) -> &mut Self {
+        self.name_generator =
+            util::NameGenerator::Seeded(Arc::new(Mutex::new(fastrand::Rng::with_seed(seed))));
+        self
+    }
+
+    /// Set the maximum number of names to try before giving up.
+    ///
+    /// A new name is tried whenever the previous one already exists. If every
+    /// attempt fails, an error of kind [`std::io::ErrorKind::AlreadyExists`] is
+    /// returned. A value of `0` is treated as `1`.
+    ///
+    /// Default: `2^31`, or `16` with a [custom generator](Self::name_generator),
+    /// which may keep returning names that already exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let named_tempfile = Builder::new()
+    ///     .retries(16)
+    ///     .tempfile(|dir, name| Path::new(dir).join(name))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.num_retries = Some(retries.max(1));
+        self
+    }
+
+    /// Hold an exclusive advisory lock on the temporary file for as long as it
+    /// is open.
+    ///
+    /// The lock marks the file as in use, so that [`reap_stale`] and
+    /// [`Builder::reap_stale_in`] can tell it apart from files left behind by
+    /// crashed processes. It is released when the last handle to the file is
+    /// closed, including by [`NamedTempFile::into_temp_path`].
+    ///
+    /// This only applies to [`Builder::tempfile`] and [`Builder::tempfile_in`].
+    ///
+    /// Default: `false`.
+    ///
+    /// # Platform Notes
+    ///
+    /// On Unix, this uses `flock` (or `fcntl` locks where `flock` is
+    /// unavailable). These locks are advisory, other processes can still open
+    /// and remove the file. Creating the file fails on platforms without
+    /// advisory file locks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let named_tempfile = Builder::new()
+    ///     .lock(true)
+    ///     .tempfile(|dir, name| Path::new(dir).join(name))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn lock(&mut self, lock: bool) -> &mut Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Remove temporary files left behind in `dir` that match this builder's
+    /// naming scheme and aren't [locked](Self::lock).
+    ///
+    /// A name matches if it starts with the prefix, ends with the suffix, and,
+    /// unless a [custom generator](Self::name_generator) is set, has exactly
+    /// [`rand_bytes`](Self::rand_bytes) characters in between. Only regular
+    /// files are removed, directories and symlinks are left alone.
+    ///
+    /// Returns the paths of the removed files.
+    ///
+    /// # Security
+    ///
+    /// Files created without locking are indistinguishable from abandoned
+    /// ones. Only use this on directories where every temporary file matching
+    /// the naming scheme is created with locking enabled.
+    ///
+    /// # Errors
+    ///
+    /// If `dir` cannot be read or a stale file cannot be removed, `Err` is
+    /// returned. On platforms without advisory file locks, this always fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let join = |dir: &_, name: &_| Path::new(dir).join(name);
+    /// let mut builder = Builder::new();
+    /// builder.prefix("my-service").lock(true);
+    ///
+    /// // Clean up after previous runs before creating new files.
+    /// builder.reap_stale_in("/var/tmp/my-service", join)?;
+    /// let file = builder.tempfile_in("/var/tmp/my-service", join)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn reap_stale_in<P: AsRef<Path>, J: Fn(&OsStr, &OsStr) -> PathBuf>(
+        &self,
+        dir: P,
+        join: J,
+    ) -> io::Result<Vec<PathBuf>> {
+        let rand_len = match self.name_generator {
+            util::NameGenerator::Custom(_) => None,
+            _ => Some(self.random_len),
+        };
+        file::reap_matching(
+            dir.as_ref(),
+            |name| util::matches_name(name, self.prefix, self.suffix, rand_len),
+            join,
+        )
+    }
+
+    /// Back unnamed temporary files with memory instead of the filesystem.
+    ///
+    /// This applies to [`Builder::tempfile_unnamed`] and to the rollover file
+    /// of [`Builder::spooled_tempfile`]. Named temporary files and directories
+    /// always live on the filesystem. See [`memfd`] for details.
+    ///
+    /// Default: `false`.
+    ///
+    /// # Platform Notes
+    ///
+    /// Memory-backed files are only supported on Linux, Android and FreeBSD.
+    /// Creating one fails elsewhere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let file = Builder::new()
+    ///     .memory_backed(true)
+    ///     .tempfile_unnamed(|dir, name| Path::new(dir).join(name))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn memory_backed(&mut self, memory_backed: bool) -> &mut Self {
+        self.memory_backed = memory_backed;
+        self
+    }
+
+    /// Allow seals to be added to memory-backed temporary files.
+    ///
+    /// Seals restrict further modifications of the file (e.g. to hand a
+    /// read-only snapshot to another process) and are added with
+    /// `rustix::fs::fcntl_add_seals`. Without this, the file can't be sealed.
+    /// Has no effect unless [`Builder::memory_backed`] is set.
+    ///
+    /// Default: `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// use std::path::Path;
+    /// use rustix::fs::{fcntl_add_seals, SealFlags};
+    /// use tempfile::Builder;
+    ///
+    /// let file = Builder::new()
+    ///     .memory_backed(true)
+    ///     .allow_sealing(true)
+    ///     .tempfile_unnamed(|dir, name| Path::new(dir).join(name))?;
+    /// fcntl_add_seals(&file, SealFlags::GROW | SealFlags::SHRINK | SealFlags::WRITE)?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn allow_sealing(&mut self, allow_sealing: bool) -> &mut Self {
+        self.allow_sealing = allow_sealing;
+        self
+    }
+
+    /// Create an unnamed temporary file.
+    ///
+    /// If [`Builder::memory_backed`] is set, the file is created in memory and
+    /// its name for debugging purposes (e.g. in `/proc/self/fd`) is the
+    /// prefix. Otherwise, this behaves like [`tempfile()`], and the other
+    /// settings of this builder are ignored.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be created, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let file = Builder::new().tempfile_unnamed(|dir, name| Path::new(dir).join(name))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn tempfile_unnamed<J: Fn(&OsStr, &OsStr) -> PathBuf>(&self, join: J) -> io::Result<std::fs::File> {
+        if self.memory_backed {
+            file::create_memfd(self.prefix, self.allow_sealing)
+        } else {
+            tempfile(join)
+        }
+    }
+
+    /// Create a spooled temporary file that rolls over to an unnamed temporary
+    /// file created by this builder.
+    ///
+    /// See [`spooled_tempfile`] and [`Builder::tempfile_unnamed`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use std::path::Path;
+    /// use tempfile::Builder;
+    ///
+    /// let mut file = Builder::new()
+    ///     .memory_backed(true)
+    ///     .spooled_tempfile(15, |dir, name| Path::new(dir).join(name));
+    /// writeln!(file, "marvin gardens, and more")?;
+    /// assert!(file.is_rolled());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn spooled_tempfile<J: Fn(&OsStr, &OsStr) -> PathBuf>(&self, max_size: usize, join: J) -> SpooledTempFile<J> {
+        let rollover = if self.memory_backed {
+            spooled::Rollover::Memory {
+                name: self.prefix.to_owned(),
+                allow_sealing: self.allow_sealing,
+            }
+        } else {
+            spooled::Rollover::Disk
+        };
+        SpooledTempFile::with_rollover(max_size, rollover, join)
+    }
+
     /// Create the named temporary file.
     ///
     /// # Security
@@ -492,15 +819,14 @@ impl<'a, 'b> Builder<'a, 'b> {
     pub fn tempfile_in<P: AsRef<Path>, J: Fn(&OsStr, &OsStr) -> PathBuf>(&self, dir: P, join: J) -> io::Result<NamedTempFile> {
         util::create_helper(
             dir.as_ref(),
-            self.prefix,
-            self.suffix,
-            self.random_len,
+            self,
             |path| {
                 file::create_named(
                     path,
                     OpenOptions::new().append(self.append),
                     self.permissions.as_ref(),
                     self.keep,
+                    self.lock,
                     &join
                 )
             },
@@ -566,7 +892,7 @@ impl<'a, 'b> Builder<'a, 'b> {
             dir = &storage;
         }
 
-        util::create_helper(dir, self.prefix, self.suffix, self.random_len, |path| {
+        util::create_helper(dir, self, |path| {
             dir::create(path, self.permissions.as_ref(), self.keep)
         }, join)
     }
@@ -686,9 +1012,7 @@ impl<'a, 'b> Builder<'a, 'b> {
     {
         util::create_helper(
             dir.as_ref(),
-            self.prefix,
-            self.suffix,
-            self.random_len,
+            self,
             move |path| {
                 Ok(NamedTempFile::from_parts(
                     f(&path)?,
@@ -1,5 +1,5 @@
-use crate::file::tempfile;
-use std::ffi::OsStr;
+use crate::file::{create_memfd, tempfile};
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
 use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
@@ -11,6 +11,15 @@ pub enum SpooledData {
     OnDisk(File),
 }
 
+/// Where a `SpooledTempFile` puts its data once it outgrows memory.
+#[derive(Debug)]
+pub(crate) enum Rollover {
+    /// An unnamed temporary file in the default temporary directory.
+    Disk,
+    /// An anonymous memory-backed file.
+    Memory { name: OsString, allow_sealing: bool },
+}
+
 /// An object that behaves like a regular temporary file, but keeps data in
 /// memory until it reaches a configured size, at which point the data is
 /// written to a temporary file on disk, and further operations use the file
@@ -19,6 +28,7 @@ pub enum SpooledData {
 pub struct SpooledTempFile<J: Fn(&OsStr, &OsStr) -> PathBuf> {
     max_size: usize,
     inner: SpooledData,
+    rollover: Rollover,
     join: J
 }
 
@@ -61,9 +71,14 @@ pub fn spooled_tempfile<J: Fn(&OsStr, &OsStr) -> PathBuf>(max_size: usize, join:
 impl<J: Fn(&OsStr, &OsStr) -> PathBuf> SpooledTempFile<J> {
     #[must_use]
     pub fn new(max_size: usize, join: J) -> SpooledTempFile<J> {
+        SpooledTempFile::with_rollover(max_size, Rollover::Disk, join)
+    }
+
+    pub(crate) fn with_rollover(max_size: usize, rollover: Rollover, join: J) -> SpooledTempFile<J> {
         SpooledTempFile {
             max_size,
             inner: SpooledData::InMemory(Cursor::new(Vec::new())),
+            rollover,
             join
         }
     }
@@ -81,7 +96,13 @@ impl<J: Fn(&OsStr, &OsStr) -> PathBuf> SpooledTempFile<J> {
     /// if already rolled over.
     pub fn roll(&mut self) -> io::Result<()> {
         if !self.is_rolled() {
-            let mut file = tempfile(&self.join)?;
+            let mut file = match &self.rollover {
+                Rollover::Disk => tempfile(&self.join)?,
+                Rollover::Memory {
+                    name,
+                    allow_sealing,
+                } => create_memfd(name, *allow_sealing)?,
+            };
             if let SpooledData::InMemory(cursor) = &mut self.inner {
                 file.write_all(cursor.get_ref())?;
                 file.seek(SeekFrom::Start(cursor.position()))?;
@@ -1,40 +1,136 @@
 use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
-use std::{io, iter::repeat_with};
+use std::sync::{Arc, Mutex};
+use std::{fmt, io, iter::repeat_with};
 
 use crate::error::IoResultExt;
+use crate::Builder;
 
-fn tmpname(prefix: &OsStr, suffix: &OsStr, rand_len: usize) -> OsString {
+/// The source of the variable part of temporary file names.
+#[derive(Clone)]
+pub(crate) enum NameGenerator {
+    /// Random alphanumeric characters from the thread-local generator.
+    Random,
+    /// Random alphanumeric characters from a generator with a fixed seed.
+    Seeded(Arc<Mutex<fastrand::Rng>>),
+    /// A user-supplied generator, called with the attempt number.
+    Custom(Arc<Mutex<dyn FnMut(u32) -> OsString + Send>>),
+}
+
+impl NameGenerator {
+    fn generate(&self, attempt: u32, rand_len: usize, buf: &mut OsString) {
+        let mut char_buf = [0u8; 4];
+        match self {
+            NameGenerator::Random => {
+                for c in repeat_with(fastrand::alphanumeric).take(rand_len) {
+                    buf.push(c.encode_utf8(&mut char_buf));
+                }
+            }
+            NameGenerator::Seeded(rng) => {
+                let mut rng = rng.lock().unwrap_or_else(|e| e.into_inner());
+                for c in repeat_with(|| rng.alphanumeric()).take(rand_len) {
+                    buf.push(c.encode_utf8(&mut char_buf));
+                }
+            }
+            NameGenerator::Custom(f) => {
+                let mut f = f.lock().unwrap_or_else(|e| e.into_inner());
+                buf.push((*f)(attempt));
+            }
+        }
+    }
+}
+
+impl fmt::Debug for NameGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameGenerator::Random => f.write_str("Random"),
+            NameGenerator::Seeded(_) => f.write_str("Seeded"),
+            NameGenerator::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl PartialEq for NameGenerator {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (NameGenerator::Random, NameGenerator::Random) => true,
+            (NameGenerator::Seeded(a), NameGenerator::Seeded(b)) => Arc::ptr_eq(a, b),
+            (NameGenerator::Custom(a), NameGenerator::Custom(b)) => {
+                Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for NameGenerator {}
+
+fn tmpname(
+    prefix: &OsStr,
+    suffix: &OsStr,
+    rand_len: usize,
+    names: &NameGenerator,
+    attempt: u32,
+) -> OsString {
     let capacity = prefix
         .len()
         .saturating_add(suffix.len())
         .saturating_add(rand_len);
     let mut buf = OsString::with_capacity(capacity);
     buf.push(prefix);
-    let mut char_buf = [0u8; 4];
-    for c in repeat_with(fastrand::alphanumeric).take(rand_len) {
-        buf.push(c.encode_utf8(&mut char_buf));
-    }
+    names.generate(attempt, rand_len, &mut buf);
     buf.push(suffix);
     buf
 }
 
-pub fn create_helper<R, J: Fn(&OsStr, &OsStr) -> PathBuf>(
-    base: &Path,
+/// Returns whether `name` could have been generated with the given prefix and
+/// suffix, and `rand_len` characters in between if known.
+///
+/// Names that aren't valid unicode never match.
+pub(crate) fn matches_name(
+    name: &OsStr,
     prefix: &OsStr,
     suffix: &OsStr,
-    random_len: usize,
+    rand_len: Option<usize>,
+) -> bool {
+    let (name, prefix, suffix) = match (name.to_str(), prefix.to_str(), suffix.to_str()) {
+        (Some(name), Some(prefix), Some(suffix)) => (name, prefix, suffix),
+        _ => return false,
+    };
+    let middle = match name
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_suffix(suffix))
+    {
+        Some(middle) => middle,
+        None => return false,
+    };
+    rand_len.map_or(true, |len| middle.chars().count() == len)
+}
+
+pub fn create_helper<R, J: Fn(&OsStr, &OsStr) -> PathBuf>(
+    base: &Path,
+    builder: &Builder<'_, '_>,
     mut f: impl FnMut(PathBuf) -> io::Result<R>,
     join: J
 ) -> io::Result<R> {
-    let num_retries = if random_len != 0 {
-        crate::NUM_RETRIES
-    } else {
-        1
+    // Without a custom generator, an empty random part always produces the
+    // same name, so there is no point in retrying. A custom generator may do the
+    // same, so it gets fewer attempts unless the limit was set explicitly.
+    let num_retries = match builder.name_generator {
+        NameGenerator::Random | NameGenerator::Seeded(_) if builder.random_len == 0 => 1,
+        NameGenerator::Custom(_) => builder.num_retries.unwrap_or(crate::NUM_CUSTOM_RETRIES),
+        _ => builder.num_retries.unwrap_or(crate::NUM_RETRIES),
     };
 
-    for _ in 0..num_retries {
-        let path = join(base.as_ref(), &tmpname(prefix, suffix, random_len));
+    for attempt in 0..num_retries {
+        let name = tmpname(
+            builder.prefix,
+            builder.suffix,
+            builder.random_len,
+            &builder.name_generator,
+            attempt,
+        );
+        let path = join(base.as_ref(), &name);
         return match f(path) {
             Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && num_retries > 1 => continue,
             // AddrInUse can happen if we're creating a UNIX domain socket and
@@ -50,3 +146,19 @@ pub fn create_helper<R, J: Fn(&OsStr, &OsStr) -> PathBuf>(
     ))
     .with_err_path(|| base)
 }
+
+/// Run a blocking filesystem operation on tokio's blocking thread pool.
+#[cfg(feature = "tokio")]
+pub(crate) async fn unblock<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(_) => panic!("blocking task cancelled by runtime shutdown"),
+        },
+    }
+}
@@ -0,0 +1,127 @@
+#![deny(rust_2018_idioms)]
+#![cfg(feature = "tokio")]
+
+use std::path::Path;
+
+use tempfile::{AsyncNamedTempFile, Builder, TempDir};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+macro_rules! transmute {
+    ($code:expr) => {
+        unsafe { std::mem::transmute::<_, &Path>($code) }
+    };
+}
+
+#[tokio::test]
+async fn test_basic() {
+    let mut tmpfile = AsyncNamedTempFile::new(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .await
+        .unwrap();
+    tmpfile.write_all(b"abcde").await.unwrap();
+    tmpfile.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+    let mut buf = String::new();
+    tmpfile.read_to_string(&mut buf).await.unwrap();
+    assert_eq!("abcde", buf);
+}
+
+#[tokio::test]
+async fn test_builder() {
+    let tmpfile = Builder::new()
+        .prefix("prefix")
+        .suffix(".txt")
+        .tempfile_async(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .await
+        .unwrap();
+    let name = tmpfile.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("prefix"));
+    assert!(name.ends_with(".txt"));
+}
+
+#[tokio::test]
+async fn test_close() {
+    let tmpfile = AsyncNamedTempFile::new(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .await
+        .unwrap();
+    let path = tmpfile.path().to_path_buf();
+    assert!(path.exists());
+    tmpfile.close().await.unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_persist() {
+    let tmp_dir = TempDir::new(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let persist_path = tmp_dir.path().join(transmute!("persisted"));
+
+    let mut tmpfile = AsyncNamedTempFile::new_in(tmp_dir.path(), |p1, p2| {
+        transmute!(p1).join(transmute!(p2))
+    })
+    .await
+    .unwrap();
+    let old_path = tmpfile.path().to_path_buf();
+    tmpfile.write_all(b"abcde").await.unwrap();
+    tmpfile.flush().await.unwrap();
+
+    let mut file = tmpfile.persist(&persist_path).await.unwrap();
+    assert!(!old_path.exists());
+    file.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).await.unwrap();
+    assert_eq!("abcde", buf);
+}
+
+#[tokio::test]
+async fn test_persist_noclobber() {
+    let tmp_dir = TempDir::new(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let persist_path = tmp_dir.path().join(transmute!("persisted"));
+    std::fs::write(&persist_path, "existing").unwrap();
+
+    let tmpfile = AsyncNamedTempFile::new_in(tmp_dir.path(), |p1, p2| {
+        transmute!(p1).join(transmute!(p2))
+    })
+    .await
+    .unwrap();
+    let err = tmpfile.persist_noclobber(&persist_path).await.unwrap_err();
+    assert!(err.file.path().exists());
+    assert_eq!(std::fs::read_to_string(&persist_path).unwrap(), "existing");
+}
+
+#[tokio::test]
+async fn test_reopen() {
+    let mut tmpfile = AsyncNamedTempFile::new(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .await
+        .unwrap();
+    let mut second = tmpfile.reopen().await.unwrap();
+    tmpfile.write_all(b"abcde").await.unwrap();
+    tmpfile.flush().await.unwrap();
+    let mut buf = String::new();
+    second.read_to_string(&mut buf).await.unwrap();
+    assert_eq!("abcde", buf);
+}
+
+#[tokio::test]
+async fn test_drop_in_runtime() {
+    let tmpfile = AsyncNamedTempFile::new(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .await
+        .unwrap();
+    let path = tmpfile.path().to_path_buf();
+    drop(tmpfile);
+
+    // The removal runs in the background, so wait for it to finish.
+    for _ in 0..100 {
+        if !path.exists() {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("temporary file was not removed");
+}
+
+#[tokio::test]
+async fn test_tempdir_close_async() {
+    let tmp_dir = TempDir::new(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let path = tmp_dir.path().to_path_buf();
+    std::fs::write(path.join(transmute!("file")), "").unwrap();
+    tmp_dir.close_async().await.unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,157 @@
+#![deny(rust_2018_idioms)]
+
+use std::fs;
+use std::path::Path;
+
+use tempfile::fixture::{assert_tree, Difference, Kind, Tree};
+
+macro_rules! transmute {
+    ($code:expr) => {
+        unsafe { std::mem::transmute::<_, &Path>($code) }
+    };
+}
+
+#[test]
+fn test_create_and_read() {
+    let tree = Tree::new()
+        .file("a/b/c.txt", "hello")
+        .dir("empty")
+        .symlink("link", "a/b/c.txt");
+    let dir = tree.create(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join(transmute!("a/b/c.txt"))).unwrap(),
+        "hello"
+    );
+    assert!(dir.path().join(transmute!("empty")).is_dir());
+
+    let actual = Tree::read(dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    assert!(tree.diff(&actual).is_empty());
+    assert_tree(dir.path(), &tree, |p1, p2| transmute!(p1).join(transmute!(p2)));
+}
+
+#[test]
+fn test_diff() {
+    let expected = Tree::new()
+        .file("same", "x")
+        .file("changed", "old")
+        .file("missing", "")
+        .dir("kind");
+    let dir = Tree::new()
+        .file("same", "x")
+        .file("changed", "new")
+        .file("kind", "")
+        .file("extra", "")
+        .create(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+
+    let actual = Tree::read(dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let diffs = expected.diff(&actual);
+    assert_eq!(diffs.len(), 4);
+    assert!(diffs.contains(&Difference::Missing {
+        path: "missing".into(),
+        kind: Kind::File,
+    }));
+    assert!(diffs.contains(&Difference::Unexpected {
+        path: "extra".into(),
+        kind: Kind::File,
+    }));
+    assert!(diffs.contains(&Difference::Kind {
+        path: "kind".into(),
+        expected: Kind::Dir,
+        actual: Kind::File,
+    }));
+    let contents = diffs
+        .iter()
+        .find(|d| matches!(d, Difference::Contents { .. }))
+        .unwrap();
+    assert_eq!(
+        contents.to_string(),
+        "~ changed: contents differ\n    expected: \"old\"\n    actual:   \"new\""
+    );
+}
+
+#[test]
+#[should_panic(expected = "+ extra (file)")]
+fn test_assert_tree_panics() {
+    let dir = Tree::new()
+        .file("extra", "")
+        .create(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    assert_tree(dir.path(), &Tree::new(), |p1, p2| transmute!(p1).join(transmute!(p2)));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree = Tree::new()
+        .file("ro/file", "")
+        .permissions("ro/file", fs::Permissions::from_mode(0o400))
+        .permissions("ro", fs::Permissions::from_mode(0o500));
+    let dir = tree.create(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    assert_tree(dir.path(), &tree, |p1, p2| transmute!(p1).join(transmute!(p2)));
+
+    let wrong = tree.clone().permissions("ro", fs::Permissions::from_mode(0o700));
+    let actual = Tree::read(dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    assert_eq!(wrong.diff(&actual).len(), 1);
+
+    // Make the directory writable again so that it can be cleaned up.
+    fs::set_permissions(
+        dir.path().join(transmute!("ro")),
+        fs::Permissions::from_mode(0o700),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_rejects_escaping_paths() {
+    let root = tempfile::tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let outside = root.path().join(transmute!("outside"));
+    let inner = root.path().join(transmute!("inner"));
+    fs::create_dir(&inner).unwrap();
+
+    for path in [outside.as_path(), Path::new("../outside"), Path::new("a/../../outside")] {
+        let err = Tree::new()
+            .file("ok", "")
+            .file(path, "escaped")
+            .write_to(&inner, |p1, p2| transmute!(p1).join(transmute!(p2)))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!outside.exists());
+        assert!(!inner.join(transmute!("ok")).exists());
+    }
+}
+
+#[test]
+fn test_rejects_paths_through_symlinks() {
+    let root = tempfile::tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let outside = root.path().join(transmute!("outside"));
+    let inner = root.path().join(transmute!("inner"));
+    fs::create_dir(&outside).unwrap();
+    fs::create_dir(&inner).unwrap();
+
+    let trees = [
+        Tree::new()
+            .symlink("escape", &outside)
+            .file("escape/pwned", "x"),
+        Tree::new()
+            .file("escape/pwned", "x")
+            .symlink("escape", &outside),
+        Tree::new().file("escape", "").file("escape/pwned", "x"),
+        Tree::new().file("escape/pwned", "x").file("escape", ""),
+    ];
+    for tree in trees {
+        let err = tree
+            .write_to(&inner, |p1, p2| transmute!(p1).join(transmute!(p2)))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!outside.join(transmute!("pwned")).exists());
+        assert!(!inner.join(transmute!("escape")).exists());
+    }
+
+    // An empty directory may still be replaced.
+    let tree = Tree::new().dir("empty").file("empty", "");
+    assert_eq!(tree.get("empty").map(|e| e.kind()), Some(Kind::File));
+}
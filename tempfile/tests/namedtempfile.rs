@@ -116,6 +116,147 @@ fn test_customnamed() {
     assert_eq!(name.len(), 18);
 }
 
+#[test]
+fn test_name_generator() {
+    let tmp_dir = tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let mut builder = Builder::new();
+    builder
+        .prefix("tmp")
+        .suffix(".rs")
+        .name_generator(|attempt| OsString::from(format!("-{}", attempt)));
+
+    let first = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    assert_eq!(first.path().file_name().unwrap(), "tmp-0.rs");
+
+    // The first name is taken, so the second attempt is used.
+    let second = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    assert_eq!(second.path().file_name().unwrap(), "tmp-1.rs");
+}
+
+#[test]
+fn test_rand_seed() {
+    let dir_a = tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let dir_b = tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+
+    let names = |dir: &Path| -> Vec<OsString> {
+        let mut builder = Builder::new();
+        builder.rand_seed(42);
+        (0..3)
+            .map(|_| {
+                builder
+                    .tempfile_in(dir, |p1, p2| transmute!(p1).join(transmute!(p2)))
+                    .unwrap()
+                    .path()
+                    .file_name()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect()
+    };
+
+    let a = names(dir_a.path());
+    assert_eq!(a, names(dir_b.path()));
+    assert_ne!(a[0], a[1]);
+}
+
+#[test]
+fn test_retries_exhausted() {
+    let tmp_dir = tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let mut builder = Builder::new();
+    builder
+        .name_generator(|_| OsString::from("fixed"))
+        .retries(3);
+
+    let _first = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    let err = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn test_custom_generator_default_retries() {
+    let tmp_dir = tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let mut builder = Builder::new();
+    let counter = attempts.clone();
+    builder.name_generator(move |_| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        OsString::from("fixed")
+    });
+
+    let _first = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    let err = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::Relaxed), 1 + 16);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_reap_stale() {
+    let tmp_dir = tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
+    let mut builder = Builder::new();
+    builder.prefix("svc").suffix(".tmp").lock(true);
+
+    let live = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    // Simulate a crashed process: the file stays behind, but its lock is gone.
+    let (_, stale) = builder
+        .tempfile_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap()
+        .keep()
+        .unwrap();
+    let other = tmp_dir.path().join(transmute!("svcXXXXXX.dat"));
+    File::create(&other).unwrap();
+    let dir = tmp_dir.path().join(transmute!("svcYYYYYY.tmp"));
+    std::fs::create_dir(&dir).unwrap();
+
+    let removed = builder
+        .reap_stale_in(tmp_dir.path(), |p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    assert_eq!(removed, vec![stale.clone()]);
+    assert!(!exists(&stale));
+    assert!(exists(live.path()));
+    assert!(exists(&other));
+    assert!(exists(&dir));
+
+    // The free function matches the default naming scheme.
+    drop(live);
+    let default = tmp_dir.path().join(transmute!("svcZZZZZZ"));
+    File::create(&default).unwrap();
+    let short = tmp_dir.path().join(transmute!("svcZZZ"));
+    File::create(&short).unwrap();
+    let removed = tempfile::reap_stale(tmp_dir.path(), "svc", |p1, p2| {
+        transmute!(p1).join(transmute!(p2))
+    })
+    .unwrap();
+    assert_eq!(removed, vec![default]);
+    assert!(exists(&short));
+    assert!(exists(&other));
+
+    // Matching on the prefix alone has to be asked for.
+    let mut removed = tempfile::reap_stale_by_prefix(tmp_dir.path(), "svc", |p1, p2| {
+        transmute!(p1).join(transmute!(p2))
+    })
+    .unwrap();
+    removed.sort();
+    let mut expected = vec![other, short];
+    expected.sort();
+    assert_eq!(removed, expected);
+    assert!(exists(&dir));
+}
+
 #[test]
 fn test_append() {
     let mut tmpfile = Builder::new().append(true).tempfile(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
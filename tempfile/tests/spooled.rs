@@ -326,3 +326,18 @@ fn test_set_len_truncation() {
     assert!(t.set_len(usize::MAX as u64 + 5).is_ok());
     assert!(t.is_rolled());
 }
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_memory_backed_rollover() {
+    let mut t = tempfile::Builder::new()
+        .memory_backed(true)
+        .spooled_tempfile(10, |p1, p2| transmute!(p1).join(transmute!(p2)));
+    t.write_all(b"abcdefghijklmnop").unwrap();
+    assert!(t.is_rolled());
+
+    let mut buf = Vec::new();
+    t.seek(SeekFrom::Start(0)).unwrap();
+    assert_eq!(t.read_to_end(&mut buf).unwrap(), 16);
+    assert_eq!(buf.as_slice(), b"abcdefghijklmnop");
+}
@@ -37,11 +37,45 @@ fn test_cleanup() {
     assert!(num_files == 0);
 }
 
+#[cfg(target_os = "linux")]
+#[test]
+fn test_memfd() {
+    let mut f = tempfile::memfd().unwrap();
+    f.write_all(b"abcde").unwrap();
+    f.seek(SeekFrom::Start(0)).unwrap();
+    let mut buf = String::new();
+    f.read_to_string(&mut buf).unwrap();
+    assert_eq!("abcde", buf);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_memory_backed_sealing() {
+    use rustix::fs::{fcntl_add_seals, fcntl_get_seals, SealFlags};
+
+    let unsealable = tempfile::Builder::new()
+        .memory_backed(true)
+        .tempfile_unnamed(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    assert!(fcntl_add_seals(&unsealable, SealFlags::WRITE).is_err());
+
+    let mut f = tempfile::Builder::new()
+        .prefix("sealed")
+        .memory_backed(true)
+        .allow_sealing(true)
+        .tempfile_unnamed(|p1, p2| transmute!(p1).join(transmute!(p2)))
+        .unwrap();
+    f.write_all(b"abcde").unwrap();
+    fcntl_add_seals(&f, SealFlags::WRITE).unwrap();
+    assert!(fcntl_get_seals(&f).unwrap().contains(SealFlags::WRITE));
+    assert!(f.write_all(b"fgh").is_err());
+}
+
 // Only run this test on Linux. MacOS doesn't like us creating so many files, apparently.
 #[cfg(target_os = "linux")]
 #[test]
 fn test_pathological_cleaner() {
-    let tmpdir = tempfile::tempdir().unwrap();
+    let tmpdir = tempfile::tempdir(|p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
     let (tx, rx) = sync_channel(0);
     let cleaner_thread = thread::spawn(move || {
         let tmp_path = rx.recv().unwrap();
@@ -62,7 +96,7 @@ fn test_pathological_cleaner() {
     tx.send(tmpdir.path().to_owned()).unwrap();
     // need 40-400 iterations to encounter race with cleaner on original system
     for _ in 0..10000 {
-        let mut tmpfile = tempfile::tempfile_in(&tmpdir).unwrap();
+        let mut tmpfile = tempfile::tempfile_in(&tmpdir, |p1, p2| transmute!(p1).join(transmute!(p2))).unwrap();
         write!(tmpfile, "abcde").unwrap();
         tmpfile.seek(SeekFrom::Start(0)).unwrap();
         let mut buf = String::new();